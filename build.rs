@@ -16,6 +16,10 @@
 // 
 
 fn main() {
+    // the pure-Rust modules are usable without libk2hash.
+    if std::env::var_os("CARGO_FEATURE_LIBK2HASH").is_none() {
+        return;
+    }
     println!("cargo:rustc-link-search=native=/usr/lib");
     println!("cargo:rustc-link-search=native=/usr/local/lib");
     println!("cargo:rustc-link-lib=dylib=k2hash");
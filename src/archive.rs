@@ -0,0 +1,321 @@
+//
+// k2hash_rust
+//
+// Copyright 2025 LY Corporation.
+//
+// Rust driver for k2hash that is a NoSQL Key Value Store(KVS) library.
+// For k2hash, see https://github.com/yahoojapan/k2hash for the details.
+//
+// For the full copyright and license information, please view
+// the license file that was distributed with this source code.
+//
+// AUTHOR:   Hirotaka Wakabayashi
+// CREATE:   Fri, 17 Jul 2025
+// REVISION:
+//
+
+//! Streaming export and import of k2hash archives.
+//!
+//! An archive uses the same layout as a transaction file with one `SetAll` record per key,
+//! so it can also be read by `TxLogReader`.
+
+use std::io::{self, Read, Write};
+use std::time::SystemTime;
+
+use crate::txlog::{bytes_to_string, read_record, serialize_record};
+use crate::{
+    read_attributes_raw, read_subkeys_raw, read_value_raw, K2hash, RawKeys, TxOperation, TxRecord,
+    TxState,
+};
+
+/// SkippedRecord represents a record which was not exported or imported.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SkippedRecord {
+    /// The byte offset of the record in the archive.
+    pub offset: u64,
+    /// The key without the terminating null, which is empty if the record is broken.
+    pub key: String,
+    /// The reason why the record was skipped.
+    pub reason: &'static str,
+}
+
+/// ArchiveSummary is the result of `ArchiveWriter::write_to` and `ArchiveReader::read_from`.
+///
+/// It is also passed to the progress callback with the counts so far.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ArchiveSummary {
+    /// The number of records exported or imported.
+    pub records: usize,
+    /// The number of keys excluded by the prefix or the filter.
+    pub filtered: usize,
+    /// The number of archive bytes written or read.
+    pub bytes: u64,
+    /// The records which were skipped because of errors.
+    pub skipped: Vec<SkippedRecord>,
+    /// Whether the archive ended at a truncated record.
+    pub truncated: bool,
+}
+
+/// A callback which receives the summary so far.
+type Progress<'a> = Box<dyn FnMut(&ArchiveSummary) + 'a>;
+
+/// A predicate which selects keys.
+type Filter<'a> = Box<dyn Fn(&str) -> bool + 'a>;
+
+/// Selects keys by a prefix and a predicate.
+struct KeySelector<'a> {
+    prefix: Option<String>,
+    filter: Option<Filter<'a>>,
+}
+
+impl KeySelector<'_> {
+    fn new() -> Self {
+        KeySelector {
+            prefix: None,
+            filter: None,
+        }
+    }
+
+    fn accepts(&self, key: &str) -> bool {
+        if let Some(ref prefix) = self.prefix {
+            if !key.starts_with(prefix.as_str()) {
+                return false;
+            }
+        }
+        match self.filter {
+            Some(ref filter) => filter(key),
+            None => true,
+        }
+    }
+}
+
+/// Counts the bytes read from a reader.
+struct CountingReader<'r, R> {
+    inner: &'r mut R,
+    count: u64,
+}
+
+impl<R: Read> Read for CountingReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.count += n as u64;
+        Ok(n)
+    }
+}
+
+/// ArchiveWriter exports the keys of a K2hash database as an archive to any writer.
+///
+/// # Examples
+///
+/// ```
+/// use k2hash_rust::{ArchiveWriter, K2hash};
+/// let db = K2hash::open_mem().expect("open_mem failed");
+/// assert!(db.set("user.1", "alice").is_ok(), "Set operation failed");
+/// assert!(db.set("tmp.1", "scratch").is_ok(), "Set operation failed");
+/// let mut archive = Vec::new();
+/// let summary = ArchiveWriter::new(&db)
+///     .prefix("user.".to_string())
+///     .write_to(&mut archive)
+///     .expect("Export failed");
+/// assert_eq!(summary.records, 1, "Only user.1 should be exported");
+/// ```
+pub struct ArchiveWriter<'a> {
+    db: &'a K2hash,
+    selector: KeySelector<'a>,
+    progress: Option<Progress<'a>>,
+}
+
+impl<'a> ArchiveWriter<'a> {
+    /// Create a new ArchiveWriter which exports all keys.
+    pub fn new(db: &'a K2hash) -> Self {
+        ArchiveWriter {
+            db,
+            selector: KeySelector::new(),
+            progress: None,
+        }
+    }
+
+    /// Export only the keys which start with the prefix.
+    pub fn prefix(mut self, prefix: String) -> Self {
+        self.selector.prefix = Some(prefix);
+        self
+    }
+
+    /// Export only the keys for which the predicate returns true.
+    pub fn filter<F: Fn(&str) -> bool + 'a>(mut self, filter: F) -> Self {
+        self.selector.filter = Some(Box::new(filter));
+        self
+    }
+
+    /// Set a callback which is called with the summary so far after each key.
+    pub fn on_progress<F: FnMut(&ArchiveSummary) + 'a>(mut self, progress: F) -> Self {
+        self.progress = Some(Box::new(progress));
+        self
+    }
+
+    /// Export the keys to the writer.
+    pub fn write_to<W: Write>(mut self, writer: &mut W) -> Result<ArchiveSummary, &'static str> {
+        let k2h = self.db.handle();
+        let mut summary = ArchiveSummary::default();
+        for key in RawKeys::new(k2h) {
+            let key_str = bytes_to_string(&key);
+            if !self.selector.accepts(&key_str) {
+                summary.filtered += 1;
+                continue;
+            }
+            let value = read_value_raw(k2h, &key);
+            let subkeys = read_subkeys_raw(k2h, &key);
+            if value.is_none() && subkeys.is_empty() {
+                // the key was removed after it was found, or its value is not readable.
+                summary.skipped.push(SkippedRecord {
+                    offset: summary.bytes,
+                    key: key_str,
+                    reason: "Failed to read the value",
+                });
+                continue;
+            }
+            let record = TxRecord {
+                offset: summary.bytes,
+                end_offset: 0,
+                timestamp: SystemTime::now(),
+                operation: TxOperation::SetAll,
+                attributes: read_attributes_raw(k2h, &key),
+                key,
+                value,
+                subkeys,
+                exdata: Vec::new(),
+            };
+            let data = serialize_record(&record);
+            writer
+                .write_all(&data)
+                .map_err(|_| "Failed to write the archive")?;
+            summary.records += 1;
+            summary.bytes += data.len() as u64;
+            if let Some(ref mut progress) = self.progress {
+                progress(&summary);
+            }
+        }
+        writer.flush().map_err(|_| "Failed to write the archive")?;
+        Ok(summary)
+    }
+}
+
+/// ArchiveReader imports an archive from any reader into a K2hash database.
+///
+/// # Examples
+///
+/// ```
+/// use k2hash_rust::{ArchiveReader, ArchiveWriter, K2hash};
+/// let src = K2hash::open_mem().expect("open_mem failed");
+/// assert!(src.set("hello", "world").is_ok(), "Set operation failed");
+/// let mut archive = Vec::new();
+/// ArchiveWriter::new(&src)
+///     .write_to(&mut archive)
+///     .expect("Export failed");
+/// let dst = K2hash::open_mem().expect("open_mem failed");
+/// let summary = ArchiveReader::new(&dst)
+///     .read_from(&mut archive.as_slice())
+///     .expect("Import failed");
+/// assert_eq!(summary.records, 1, "One record should be imported");
+/// assert_eq!(dst.get("hello").unwrap(), Some("world".to_string()));
+/// ```
+pub struct ArchiveReader<'a> {
+    db: &'a K2hash,
+    selector: KeySelector<'a>,
+    progress: Option<Progress<'a>>,
+    skip_error: bool,
+}
+
+impl<'a> ArchiveReader<'a> {
+    /// Create a new ArchiveReader which imports all records and skips errors.
+    pub fn new(db: &'a K2hash) -> Self {
+        ArchiveReader {
+            db,
+            selector: KeySelector::new(),
+            progress: None,
+            skip_error: true,
+        }
+    }
+
+    /// Import only the keys which start with the prefix.
+    pub fn prefix(mut self, prefix: String) -> Self {
+        self.selector.prefix = Some(prefix);
+        self
+    }
+
+    /// Import only the keys for which the predicate returns true.
+    pub fn filter<F: Fn(&str) -> bool + 'a>(mut self, filter: F) -> Self {
+        self.selector.filter = Some(Box::new(filter));
+        self
+    }
+
+    /// Set a callback which is called with the summary so far after each record.
+    pub fn on_progress<F: FnMut(&ArchiveSummary) + 'a>(mut self, progress: F) -> Self {
+        self.progress = Some(Box::new(progress));
+        self
+    }
+
+    /// Set whether errors are recorded in the summary instead of stopping the import.
+    ///
+    /// A broken record stops the import in both cases because the next record cannot be found.
+    pub fn skip_error(mut self, skip_error: bool) -> Self {
+        self.skip_error = skip_error;
+        self
+    }
+
+    /// Import the records from the reader.
+    pub fn read_from<R: Read>(mut self, reader: &mut R) -> Result<ArchiveSummary, &'static str> {
+        let mut state = TxState::new(self.db.handle(), false);
+        let mut reader = CountingReader {
+            inner: reader,
+            count: 0,
+        };
+        let mut summary = ArchiveSummary::default();
+        loop {
+            let record = match read_record(&mut reader, summary.bytes) {
+                Ok(Some(record)) => record,
+                Ok(None) => {
+                    summary.truncated = reader.count > summary.bytes;
+                    break;
+                }
+                Err(e) if self.skip_error => {
+                    summary.skipped.push(SkippedRecord {
+                        offset: summary.bytes,
+                        key: String::new(),
+                        reason: e,
+                    });
+                    break;
+                }
+                Err(e) => return Err(e),
+            };
+            summary.bytes = record.end_offset;
+            let key = record.key_str();
+            if !self.selector.accepts(&key) {
+                summary.filtered += 1;
+                continue;
+            }
+            match state.apply(&record) {
+                Ok(_) => summary.records += 1,
+                Err(e) if self.skip_error => summary.skipped.push(SkippedRecord {
+                    offset: record.offset,
+                    key,
+                    reason: e,
+                }),
+                Err(e) => return Err(e),
+            }
+            if let Some(ref mut progress) = self.progress {
+                progress(&summary);
+            }
+        }
+        Ok(summary)
+    }
+}
+
+//
+// Local variables:
+// tab-width: 4
+// c-basic-offset: 4
+// End:
+// vim600: expandtab sw=4 ts=4 fdm=marker
+// vim<600: expandtab sw=4 ts=4
+//
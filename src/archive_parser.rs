@@ -0,0 +1,140 @@
+//
+// k2hash_rust
+//
+// Copyright 2025 LY Corporation.
+//
+// Rust driver for k2hash that is a NoSQL Key Value Store(KVS) library.
+// For k2hash, see https://github.com/yahoojapan/k2hash for the details.
+//
+// For the full copyright and license information, please view
+// the license file that was distributed with this source code.
+//
+// AUTHOR:   Hirotaka Wakabayashi
+// CREATE:   Fri, 17 Jul 2025
+// REVISION:
+//
+
+//! Pure-Rust parser of k2hash archives for offline inspection.
+//!
+//! An archive written by `K2hash::dump_to_file` is a sequence of `SetAll` records in the
+//! transaction file layout, so this module needs neither libk2hash nor a K2hash handle.
+
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+use crate::txlog::{bytes_to_string, read_record};
+use crate::TxOperation;
+
+/// ArchiveEntry represents a key stored in a k2hash archive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchiveEntry {
+    /// The byte offset of the entry in the archive.
+    pub offset: u64,
+    /// The key.
+    pub key: Vec<u8>,
+    /// The value, which is empty if the key has no value.
+    pub value: Vec<u8>,
+    /// The subkeys.
+    pub subkeys: Vec<Vec<u8>>,
+    /// The attributes.
+    pub attributes: Vec<(Vec<u8>, Vec<u8>)>,
+}
+
+impl ArchiveEntry {
+    /// Get the key as a string without the terminating null.
+    pub fn key_str(&self) -> String {
+        bytes_to_string(&self.key)
+    }
+
+    /// Get the value as a string without the terminating null.
+    pub fn value_str(&self) -> String {
+        bytes_to_string(&self.value)
+    }
+
+    /// Get the subkeys as strings without the terminating null.
+    pub fn subkey_strs(&self) -> Vec<String> {
+        self.subkeys.iter().map(|k| bytes_to_string(k)).collect()
+    }
+
+    /// Get the attributes as strings without the terminating null.
+    pub fn attribute_strs(&self) -> Vec<(String, String)> {
+        self.attributes
+            .iter()
+            .map(|(k, v)| (bytes_to_string(k), bytes_to_string(v)))
+            .collect()
+    }
+}
+
+/// ArchiveParser iterates the entries of a k2hash archive without libk2hash.
+///
+/// # Examples
+///
+/// ```no_run
+/// use k2hash_rust::ArchiveParser;
+/// let parser = ArchiveParser::open("dump.k2har").expect("open failed");
+/// for entry in parser {
+///     let entry = entry.expect("broken archive");
+///     println!("{} => {}", entry.key_str(), entry.value_str());
+/// }
+/// ```
+pub struct ArchiveParser<R> {
+    reader: R,
+    offset: u64,
+    failed: bool,
+}
+
+impl ArchiveParser<BufReader<File>> {
+    /// Open an archive file.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, &'static str> {
+        let file = File::open(path).map_err(|_| "Failed to open the archive file")?;
+        Ok(ArchiveParser::new(BufReader::new(file)))
+    }
+}
+
+impl<R: Read> ArchiveParser<R> {
+    /// Create a new ArchiveParser reading from the reader.
+    pub fn new(reader: R) -> Self {
+        ArchiveParser {
+            reader,
+            offset: 0,
+            failed: false,
+        }
+    }
+}
+
+impl<R: Read> Iterator for ArchiveParser<R> {
+    type Item = Result<ArchiveEntry, &'static str>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let result = match read_record(&mut self.reader, self.offset) {
+            Ok(Some(record)) if record.operation == TxOperation::SetAll => {
+                self.offset = record.end_offset;
+                return Some(Ok(ArchiveEntry {
+                    offset: record.offset,
+                    key: record.key,
+                    value: record.value.unwrap_or_default(),
+                    subkeys: record.subkeys,
+                    attributes: record.attributes,
+                }));
+            }
+            Ok(Some(_)) => Err("unexpected operation in the archive"),
+            Ok(None) => return None,
+            Err(e) => Err(e),
+        };
+        self.failed = true;
+        Some(result)
+    }
+}
+
+//
+// Local variables:
+// tab-width: 4
+// c-basic-offset: 4
+// End:
+// vim600: expandtab sw=4 ts=4 fdm=marker
+// vim<600: expandtab sw=4 ts=4
+//
@@ -0,0 +1,502 @@
+//
+// k2hash_rust
+//
+// Copyright 2025 LY Corporation.
+//
+// Rust driver for k2hash that is a NoSQL Key Value Store(KVS) library.
+// For k2hash, see https://github.com/yahoojapan/k2hash for the details.
+//
+// For the full copyright and license information, please view
+// the license file that was distributed with this source code.
+//
+// AUTHOR:   Hirotaka Wakabayashi
+// CREATE:   Fri, 17 Jul 2025
+// REVISION:
+//
+
+//! Async facade of K2hash, Queue and KeyQueue for tokio.
+//!
+//! Every k2hash call may block on mmap page faults and file locks, so each method runs the
+//! blocking call on the tokio blocking thread pool via `spawn_blocking`.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use tokio::sync::{mpsc, oneshot};
+use tokio::task;
+use tokio::time::Sleep;
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_stream::Stream;
+
+use crate::{K2hash, K2hashKey, KeyQueue, Queue};
+
+/// The number of keys buffered by the key stream.
+const STREAM_BUFFER: usize = 64;
+
+/// Runs a blocking closure on the tokio blocking thread pool.
+async fn run_blocking<T, F>(f: F) -> Result<T, &'static str>
+where
+    F: FnOnce() -> Result<T, &'static str> + Send + 'static,
+    T: Send + 'static,
+{
+    task::spawn_blocking(f)
+        .await
+        .map_err(|_| "blocking task failed")?
+}
+
+/// AsyncK2hash provides async versions of the main K2hash methods.
+///
+/// # Examples
+///
+/// ```
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// use k2hash_rust::AsyncK2hash;
+/// let db = AsyncK2hash::open_mem().await.expect("open_mem failed");
+/// assert!(db.set("hello", "world").await.is_ok(), "Set operation failed");
+/// assert_eq!(
+///     db.get("hello").await.expect("Get operation failed"),
+///     Some("world".to_string())
+/// );
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct AsyncK2hash {
+    inner: Arc<K2hash>,
+}
+
+impl AsyncK2hash {
+    /// Create a new AsyncK2hash from an opened K2hash.
+    pub fn new(db: K2hash) -> Self {
+        AsyncK2hash {
+            inner: Arc::new(db),
+        }
+    }
+
+    /// Open a key-value database in a file based.
+    pub async fn open(file: &str) -> Result<Self, &'static str> {
+        let file = file.to_string();
+        run_blocking(move || K2hash::open(&file))
+            .await
+            .map(Self::new)
+    }
+
+    /// Open a memory-based database.
+    pub async fn open_mem() -> Result<Self, &'static str> {
+        run_blocking(K2hash::open_mem).await.map(Self::new)
+    }
+
+    /// Get the handle of the K2hash database.
+    pub fn handle(&self) -> u64 {
+        self.inner.handle()
+    }
+
+    /// Get the blocking K2hash.
+    pub fn blocking(&self) -> &K2hash {
+        &self.inner
+    }
+
+    /// Runs a closure with the blocking K2hash on the blocking thread pool.
+    async fn run<T, F>(&self, f: F) -> Result<T, &'static str>
+    where
+        F: FnOnce(&K2hash) -> Result<T, &'static str> + Send + 'static,
+        T: Send + 'static,
+    {
+        let db = Arc::clone(&self.inner);
+        run_blocking(move || f(&db)).await
+    }
+
+    /// Set a value with a key.
+    pub async fn set(&self, key: &str, value: &str) -> Result<(), &'static str> {
+        self.set_with_options(key, value, None, None).await
+    }
+
+    /// Set a value with a key with the options.
+    pub async fn set_with_options(
+        &self,
+        key: &str,
+        value: &str,
+        password: Option<&str>,
+        expire_duration: Option<u64>,
+    ) -> Result<(), &'static str> {
+        let (key, value) = (key.to_string(), value.to_string());
+        let password = password.map(|p| p.to_string());
+        self.run(move |db| db.set_with_options(&key, &value, password.as_deref(), expire_duration))
+            .await
+    }
+
+    /// Get a value from a key.
+    pub async fn get(&self, key: &str) -> Result<Option<String>, &'static str> {
+        self.get_with_options(key, None).await
+    }
+
+    /// Get a value from a key with options.
+    pub async fn get_with_options(
+        &self,
+        key: &str,
+        password: Option<&str>,
+    ) -> Result<Option<String>, &'static str> {
+        let key = key.to_string();
+        let password = password.map(|p| p.to_string());
+        self.run(move |db| db.get_with_options(&key, password.as_deref()))
+            .await
+    }
+
+    /// Remove a key from the K2hash database.
+    pub async fn remove(&self, key: &str) -> Result<(), &'static str> {
+        self.remove_with_options(key, false).await
+    }
+
+    /// Remove a key from the K2hash database with options.
+    pub async fn remove_with_options(
+        &self,
+        key: &str,
+        remove_all_subkeys: bool,
+    ) -> Result<(), &'static str> {
+        let key = key.to_string();
+        self.run(move |db| db.remove_with_options(&key, remove_all_subkeys))
+            .await
+    }
+
+    /// Rename a old key with a new key.
+    pub async fn rename(&self, oldkey: &str, newkey: &str) -> Result<(), &'static str> {
+        let (oldkey, newkey) = (oldkey.to_string(), newkey.to_string());
+        self.run(move |db| db.rename(&oldkey, &newkey)).await
+    }
+
+    /// Add a subkey to a key.
+    pub async fn add_subkey(
+        &self,
+        key: &str,
+        subkey: &str,
+        subval: &str,
+    ) -> Result<(), &'static str> {
+        let (key, subkey, subval) = (key.to_string(), subkey.to_string(), subval.to_string());
+        self.run(move |db| db.add_subkey(&key, &subkey, &subval))
+            .await
+    }
+
+    /// Get subkeys of a key.
+    pub async fn get_subkeys(&self, key: &str) -> Result<Option<Vec<String>>, &'static str> {
+        let key = key.to_string();
+        self.run(move |db| db.get_subkeys(&key)).await
+    }
+
+    /// Remove a subkey of a key from the K2hash database.
+    pub async fn remove_subkeys(&self, key: &str, subkeys: Vec<&str>) -> Result<(), &'static str> {
+        let key = key.to_string();
+        let subkeys: Vec<String> = subkeys.into_iter().map(|s| s.to_string()).collect();
+        self.run(move |db| db.remove_subkeys(&key, subkeys.iter().map(|s| s.as_str()).collect()))
+            .await
+    }
+
+    /// Get attributes of a key.
+    pub async fn get_attributes(
+        &self,
+        key: &str,
+    ) -> Result<Option<HashMap<String, String>>, &'static str> {
+        let key = key.to_string();
+        self.run(move |db| db.get_attributes(&key)).await
+    }
+
+    /// Set attribute of a key in the K2hash database.
+    pub async fn set_attribute(
+        &self,
+        key: &str,
+        attr_name: &str,
+        attr_val: &str,
+    ) -> Result<(), &'static str> {
+        let (key, attr_name, attr_val) =
+            (key.to_string(), attr_name.to_string(), attr_val.to_string());
+        self.run(move |db| db.set_attribute(&key, &attr_name, &attr_val))
+            .await
+    }
+
+    /// Get a stream over the keys in the K2hash database.
+    pub fn keys(&self) -> impl Stream<Item = String> + Send + Unpin + 'static {
+        let (tx, rx) = mpsc::channel(STREAM_BUFFER);
+        let db = Arc::clone(&self.inner);
+        task::spawn_blocking(move || {
+            let keys = match K2hashKey::new(db.handle(), None) {
+                Ok(keys) => keys,
+                Err(_) => return, // no keys
+            };
+            for key in keys {
+                if tx.blocking_send(key).is_err() {
+                    break; // the stream was dropped
+                }
+            }
+        });
+        ReceiverStream::new(rx)
+    }
+}
+
+/// AsyncQueue provides async versions of the Queue methods.
+///
+/// # Examples
+///
+/// ```
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// use k2hash_rust::{AsyncQueue, K2hash, Queue};
+/// let db = K2hash::open_mem().expect("open_mem failed");
+/// let q = Queue::new(db.handle(), true, None, None, None).expect("Queue creation failed");
+/// let q = AsyncQueue::new(q);
+/// assert!(q.put("hello").await.is_ok(), "Push operation failed");
+/// assert_eq!(q.get().await, Some("hello".to_string()));
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct AsyncQueue {
+    inner: Arc<Queue>,
+}
+
+impl AsyncQueue {
+    /// Create a new AsyncQueue from a Queue.
+    pub fn new(queue: Queue) -> Self {
+        AsyncQueue {
+            inner: Arc::new(queue),
+        }
+    }
+
+    /// Get a handle to the Queue.
+    pub fn handle(&self) -> u64 {
+        self.inner.handle()
+    }
+
+    /// Get the blocking Queue.
+    pub fn blocking(&self) -> &Queue {
+        &self.inner
+    }
+
+    /// Put a value into the Queue.
+    pub async fn put(&self, value: &str) -> Result<(), &'static str> {
+        let (q, value) = (Arc::clone(&self.inner), value.to_string());
+        run_blocking(move || q.put(&value)).await
+    }
+
+    /// Get a value from the Queue.
+    pub async fn get(&self) -> Option<String> {
+        let q = Arc::clone(&self.inner);
+        run_blocking(move || Ok(q.get())).await.ok().flatten()
+    }
+
+    /// Get the size of the Queue.
+    pub async fn qsize(&self) -> usize {
+        let q = Arc::clone(&self.inner);
+        run_blocking(move || Ok(q.qsize())).await.unwrap_or(0)
+    }
+
+    /// Check if the Queue is empty.
+    pub async fn empty(&self) -> bool {
+        let q = Arc::clone(&self.inner);
+        run_blocking(move || Ok(q.empty())).await.unwrap_or(true)
+    }
+
+    /// Remove the objects from the Queue.
+    pub async fn remove(&self, count: usize) -> Result<Vec<String>, &'static str> {
+        let q = Arc::clone(&self.inner);
+        run_blocking(move || q.remove(count)).await
+    }
+
+    /// Get a stream which pops values from the Queue.
+    ///
+    /// A value is popped only when the stream is polled, so dropping the stream leaves the
+    /// other values in the Queue. The stream polls the Queue every `poll_interval` while it is
+    /// empty and never ends until it is dropped.
+    pub fn into_stream(self, poll_interval: Duration) -> QueueStream<String> {
+        let (pop, push) = (Arc::clone(&self.inner), self.inner);
+        QueueStream::new(
+            Arc::new(move || pop.get()),
+            Arc::new(move |value: String| {
+                let _ = push.put(&value);
+            }),
+            poll_interval,
+        )
+    }
+}
+
+/// AsyncKeyQueue provides async versions of the KeyQueue methods.
+///
+/// # Examples
+///
+/// ```
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// use k2hash_rust::{AsyncKeyQueue, K2hash, KeyQueue};
+/// let db = K2hash::open_mem().expect("open_mem failed");
+/// let q = KeyQueue::new(db.handle(), true, None, None, None).expect("KeyQueue creation failed");
+/// let q = AsyncKeyQueue::new(q);
+/// assert!(q.put("hello", "world").await.is_ok(), "Push operation failed");
+/// assert_eq!(q.get().await, Some(("hello".to_string(), "world".to_string())));
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct AsyncKeyQueue {
+    inner: Arc<KeyQueue>,
+}
+
+impl AsyncKeyQueue {
+    /// Create a new AsyncKeyQueue from a KeyQueue.
+    pub fn new(queue: KeyQueue) -> Self {
+        AsyncKeyQueue {
+            inner: Arc::new(queue),
+        }
+    }
+
+    /// Get the handle of the KeyQueue.
+    pub fn handle(&self) -> u64 {
+        self.inner.handle()
+    }
+
+    /// Get the blocking KeyQueue.
+    pub fn blocking(&self) -> &KeyQueue {
+        &self.inner
+    }
+
+    /// Put a key-value pair into the KeyQueue.
+    pub async fn put(&self, key: &str, value: &str) -> Result<(), &'static str> {
+        let (q, key, value) = (Arc::clone(&self.inner), key.to_string(), value.to_string());
+        run_blocking(move || q.put(&key, &value)).await
+    }
+
+    /// Get a key-value pair from the KeyQueue.
+    pub async fn get(&self) -> Option<(String, String)> {
+        let q = Arc::clone(&self.inner);
+        run_blocking(move || Ok(q.get())).await.ok().flatten()
+    }
+
+    /// Get the size of the KeyQueue.
+    pub async fn qsize(&self) -> usize {
+        let q = Arc::clone(&self.inner);
+        run_blocking(move || Ok(q.qsize())).await.unwrap_or(0)
+    }
+
+    /// Check if the KeyQueue is empty.
+    pub async fn empty(&self) -> bool {
+        let q = Arc::clone(&self.inner);
+        run_blocking(move || Ok(q.empty())).await.unwrap_or(true)
+    }
+
+    /// Remove elements from the KeyQueue.
+    pub async fn remove(&self, count: usize) -> Result<Vec<(String, String)>, &'static str> {
+        let q = Arc::clone(&self.inner);
+        run_blocking(move || q.remove(count)).await
+    }
+
+    /// Get a stream which pops key-value pairs from the KeyQueue.
+    ///
+    /// A pair is popped only when the stream is polled, so dropping the stream leaves the
+    /// other pairs in the KeyQueue. The stream polls the KeyQueue every `poll_interval` while
+    /// it is empty and never ends until it is dropped.
+    pub fn into_stream(self, poll_interval: Duration) -> QueueStream<(String, String)> {
+        let (pop, push) = (Arc::clone(&self.inner), self.inner);
+        QueueStream::new(
+            Arc::new(move || pop.get()),
+            Arc::new(move |(key, value): (String, String)| {
+                let _ = push.put(&key, &value);
+            }),
+            poll_interval,
+        )
+    }
+}
+
+/// Pops an item from a queue.
+type PopFn<T> = Arc<dyn Fn() -> Option<T> + Send + Sync>;
+
+/// Puts an item back to a queue.
+type PushFn<T> = Arc<dyn Fn(T) + Send + Sync>;
+
+/// The state of a QueueStream.
+enum PopState<T> {
+    /// No pop is running.
+    Idle,
+    /// A pop is running on the blocking thread pool.
+    Popping(oneshot::Receiver<Option<T>>),
+    /// The queue was empty.
+    Sleeping(Pin<Box<Sleep>>),
+}
+
+/// QueueStream pops items from a queue each time it is polled.
+///
+/// At most one item is popped ahead of the consumer. An item popped for a stream which is
+/// dropped before receiving it is put back to the queue.
+pub struct QueueStream<T> {
+    pop: PopFn<T>,
+    push: PushFn<T>,
+    poll_interval: Duration,
+    state: PopState<T>,
+}
+
+impl<T: Send + 'static> QueueStream<T> {
+    fn new(pop: PopFn<T>, push: PushFn<T>, poll_interval: Duration) -> Self {
+        QueueStream {
+            pop,
+            push,
+            poll_interval,
+            state: PopState::Idle,
+        }
+    }
+}
+
+impl<T: Send + 'static> Stream for QueueStream<T> {
+    type Item = T;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        let this = self.get_mut();
+        loop {
+            match this.state {
+                PopState::Idle => {
+                    let (tx, rx) = oneshot::channel();
+                    let (pop, push) = (Arc::clone(&this.pop), Arc::clone(&this.push));
+                    task::spawn_blocking(move || {
+                        if let Err(Some(item)) = tx.send(pop()) {
+                            push(item); // the stream was dropped
+                        }
+                    });
+                    this.state = PopState::Popping(rx);
+                }
+                PopState::Popping(ref mut rx) => match Pin::new(rx).poll(cx) {
+                    Poll::Pending => return Poll::Pending,
+                    Poll::Ready(Ok(Some(item))) => {
+                        this.state = PopState::Idle;
+                        return Poll::Ready(Some(item));
+                    }
+                    Poll::Ready(Ok(None)) => {
+                        this.state =
+                            PopState::Sleeping(Box::pin(tokio::time::sleep(this.poll_interval)));
+                    }
+                    Poll::Ready(Err(_)) => return Poll::Ready(None), // the pop panicked
+                },
+                PopState::Sleeping(ref mut sleep) => match sleep.as_mut().poll(cx) {
+                    Poll::Pending => return Poll::Pending,
+                    Poll::Ready(()) => this.state = PopState::Idle,
+                },
+            }
+        }
+    }
+}
+
+impl<T> Drop for QueueStream<T> {
+    fn drop(&mut self) {
+        // an item which was popped but not received yet goes back to the queue.
+        if let PopState::Popping(ref mut rx) = self.state {
+            if let Ok(Some(item)) = rx.try_recv() {
+                (self.push)(item);
+            }
+        }
+    }
+}
+
+//
+// Local variables:
+// tab-width: 4
+// c-basic-offset: 4
+// End:
+// vim600: expandtab sw=4 ts=4 fdm=marker
+// vim<600: expandtab sw=4 ts=4
+//
@@ -0,0 +1,295 @@
+//
+// k2hash_rust
+//
+// Copyright 2025 LY Corporation.
+//
+// Rust driver for k2hash that is a NoSQL Key Value Store(KVS) library.
+// For k2hash, see https://github.com/yahoojapan/k2hash for the details.
+//
+// For the full copyright and license information, please view
+// the license file that was distributed with this source code.
+//
+// AUTHOR:   Hirotaka Wakabayashi
+// CREATE:   Fri, 17 Jul 2025
+// REVISION:
+//
+
+//! Online backup of a K2hash database into a new k2hash file.
+
+use std::fs;
+use std::path::Path;
+use std::time::SystemTime;
+
+use crate::metadata::{attribute_name, decode_timespec, MTIME_ATTR};
+use crate::{
+    read_attributes_raw, read_subkeys_raw, read_value_raw, set_all_raw, ApplyTxOptions, K2hash,
+    RawKeys,
+};
+
+/// BackupOptions holds the options of `K2hash::backup_to`.
+///
+/// # Examples
+///
+/// ```
+/// use k2hash_rust::BackupOptions;
+/// use std::time::{Duration, SystemTime};
+/// let options = BackupOptions::new()
+///     .snapshot(true)
+///     .since(SystemTime::now() - Duration::from_secs(3600));
+/// ```
+#[derive(Debug, Clone)]
+pub struct BackupOptions {
+    maskbit: i32,
+    cmaskbit: i32,
+    maxelementcnt: i32,
+    pagesize: usize,
+    snapshot: bool,
+    verify: bool,
+    since: Option<SystemTime>,
+}
+
+impl Default for BackupOptions {
+    fn default() -> Self {
+        BackupOptions {
+            maskbit: 8,
+            cmaskbit: 4,
+            maxelementcnt: 1024,
+            pagesize: 512,
+            snapshot: false,
+            verify: true,
+            since: None,
+        }
+    }
+}
+
+impl BackupOptions {
+    /// Create a new BackupOptions for a verified full backup.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the parameters of `K2hash::create_with_options` for a new backup file.
+    pub fn create_params(
+        mut self,
+        maskbit: i32,
+        cmaskbit: i32,
+        maxelementcnt: i32,
+        pagesize: usize,
+    ) -> Self {
+        self.maskbit = maskbit;
+        self.cmaskbit = cmaskbit;
+        self.maxelementcnt = maxelementcnt;
+        self.pagesize = pagesize;
+        self
+    }
+
+    /// Set the snapshot flag which makes the backup consistent as of its end.
+    ///
+    /// The changes made while copying are recorded in a transaction file next to the backup
+    /// and replayed into it. `K2hash::backup_to` refuses a snapshot of a database which has
+    /// its own transaction enabled, because k2hash keeps one transaction file per handle.
+    pub fn snapshot(mut self, snapshot: bool) -> Self {
+        self.snapshot = snapshot;
+        self
+    }
+
+    /// Set the verify flag which reads the copied keys back and compares their checksums and
+    /// the number of keys in the backup.
+    pub fn verify(mut self, verify: bool) -> Self {
+        self.verify = verify;
+        self
+    }
+
+    /// Copy only the keys modified at or after the time into an existing or new backup.
+    ///
+    /// This relies on the mtime attribute enabled by `K2hash::enable_mtime`, so keys without
+    /// it are always copied. Removed keys are not removed from the backup.
+    pub fn since(mut self, since: SystemTime) -> Self {
+        self.since = Some(since);
+        self
+    }
+}
+
+/// BackupReport is the result of `K2hash::backup_to`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BackupReport {
+    /// The number of copied keys.
+    pub keys: usize,
+    /// The number of keys which were not modified since the time of the incremental mode.
+    pub unmodified: usize,
+    /// The number of keys which were removed between listing and copying, so were not copied.
+    pub skipped: usize,
+    /// The number of records replayed from the snapshot transaction file.
+    pub replayed: usize,
+    /// The checksum of the copied keys, values, subkeys and attributes.
+    pub checksum: u64,
+}
+
+/// Removes the snapshot transaction file and a backup file created by `K2hash::backup_to`
+/// unless the backup succeeded.
+struct BackupCleanup<'a> {
+    txfile: &'a str,
+    created: Option<&'a str>,
+    done: bool,
+}
+
+impl Drop for BackupCleanup<'_> {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(self.txfile);
+        if let (false, Some(path)) = (self.done, self.created) {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+/// Returns the modification time of a key recorded in its attributes.
+fn mtime_of(attrs: &[(Vec<u8>, Vec<u8>)]) -> Option<SystemTime> {
+    let (_, val) = attrs
+        .iter()
+        .find(|(name, _)| attribute_name(name) == MTIME_ATTR)?;
+    decode_timespec(val)
+}
+
+/// Updates a FNV-1a checksum with a length prefixed field.
+fn checksum_update(hash: &mut u64, bytes: &[u8]) {
+    for b in (bytes.len() as u64).to_le_bytes().iter().chain(bytes) {
+        *hash ^= *b as u64;
+        *hash = hash.wrapping_mul(0x100000001b3);
+    }
+}
+
+/// Returns the checksum of a key, its value, subkeys and attributes.
+fn entry_checksum(
+    key: &[u8],
+    value: &[u8],
+    subkeys: &[Vec<u8>],
+    attrs: &[(Vec<u8>, Vec<u8>)],
+) -> u64 {
+    let mut hash = 0xcbf29ce484222325;
+    checksum_update(&mut hash, key);
+    checksum_update(&mut hash, value);
+    for subkey in subkeys {
+        checksum_update(&mut hash, subkey);
+    }
+    // the attributes are sorted, because their order is not part of the key.
+    let mut attrs: Vec<_> = attrs.iter().collect();
+    attrs.sort();
+    for (name, val) in attrs {
+        checksum_update(&mut hash, name);
+        checksum_update(&mut hash, val);
+    }
+    hash
+}
+
+impl K2hash {
+    /// Back up the database into a k2hash file, which is created if it does not exist.
+    ///
+    /// The keys are copied while the database is in use. Set `BackupOptions::snapshot` for a
+    /// consistent copy, otherwise a key changed while copying may be copied before or after
+    /// the change. A key removed while copying is skipped and counted in the report.
+    ///
+    /// On an error, the snapshot transaction file and a backup file created by this call are
+    /// removed. An existing backup file is kept, and may hold some of the keys copied so far.
+    pub fn backup_to(
+        &self,
+        path: &str,
+        options: BackupOptions,
+    ) -> Result<BackupReport, &'static str> {
+        if options.snapshot && self.get_tx_file_fd().is_ok() {
+            return Err("snapshot needs a database without a transaction enabled");
+        }
+        let created = !Path::new(path).exists();
+        if created {
+            K2hash::create_with_options(
+                path,
+                options.maskbit,
+                options.cmaskbit,
+                options.maxelementcnt,
+                options.pagesize,
+            )?;
+        }
+        let txfile = format!("{}.snapshot", path);
+        // declared before the handles, so it is dropped after they are closed.
+        let mut cleanup = BackupCleanup {
+            txfile: &txfile,
+            created: created.then_some(path),
+            done: false,
+        };
+        let backup = K2hash::open(path)?;
+        let tx = if options.snapshot {
+            let _ = fs::remove_file(&txfile);
+            Some(self.transaction(&txfile)?)
+        } else {
+            None
+        };
+
+        let mut report = BackupReport::default();
+        let mut copied = Vec::new();
+        for key in RawKeys::new(self.handle) {
+            let attrs = read_attributes_raw(self.handle, &key);
+            if let (Some(since), Some(mtime)) = (options.since, mtime_of(&attrs)) {
+                if mtime < since {
+                    report.unmodified += 1;
+                    continue;
+                }
+            }
+            let Some(value) = read_value_raw(self.handle, &key) else {
+                report.skipped += 1;
+                continue;
+            };
+            let subkeys = read_subkeys_raw(self.handle, &key);
+            if !set_all_raw(backup.handle, &key, &value, &subkeys, &attrs) {
+                return Err("Failed to copy a key into the backup");
+            }
+            // xor keeps the checksum independent of the order of keys.
+            report.checksum ^= entry_checksum(&key, &value, &subkeys, &attrs);
+            report.keys += 1;
+            if options.verify {
+                copied.push(key);
+            }
+        }
+
+        if options.verify {
+            let mut checksum = 0;
+            for key in copied.iter() {
+                let Some(value) = read_value_raw(backup.handle, key) else {
+                    return Err("Backup verification failed: missing key");
+                };
+                checksum ^= entry_checksum(
+                    key,
+                    &value,
+                    &read_subkeys_raw(backup.handle, key),
+                    &read_attributes_raw(backup.handle, key),
+                );
+            }
+            if checksum != report.checksum {
+                return Err("Backup verification failed");
+            }
+            // a new full backup holds exactly the copied keys, others may hold older ones too.
+            let backup_keys = RawKeys::new(backup.handle).count();
+            let expected_exact = created && options.since.is_none();
+            if backup_keys < report.keys || (expected_exact && backup_keys != report.keys) {
+                return Err("Backup verification failed: key count mismatch");
+            }
+        }
+
+        if let Some(tx) = tx {
+            tx.finish()?;
+            report.replayed = backup
+                .apply_tx_log(&txfile, ApplyTxOptions::new())?
+                .changes
+                .len();
+        }
+        cleanup.done = true;
+        Ok(report)
+    }
+}
+
+//
+// Local variables:
+// tab-width: 4
+// c-basic-offset: 4
+// End:
+// vim600: expandtab sw=4 ts=4 fdm=marker
+// vim<600: expandtab sw=4 ts=4
+//
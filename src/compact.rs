@@ -0,0 +1,218 @@
+//
+// k2hash_rust
+//
+// Copyright 2025 LY Corporation.
+//
+// Rust driver for k2hash that is a NoSQL Key Value Store(KVS) library.
+// For k2hash, see https://github.com/yahoojapan/k2hash for the details.
+//
+// For the full copyright and license information, please view
+// the license file that was distributed with this source code.
+//
+// AUTHOR:   Hirotaka Wakabayashi
+// CREATE:   Fri, 17 Jul 2025
+// REVISION:
+//
+
+//! Compaction of K2hash databases by rebuilding them into new files.
+
+use std::fs;
+use std::path::Path;
+
+use crate::{BackupOptions, K2hash};
+
+/// CompactOptions holds the table parameters of a compacted database.
+///
+/// # Examples
+///
+/// ```
+/// use k2hash_rust::CompactOptions;
+/// let options = CompactOptions::new().maskbit(10).maxelementcnt(2048);
+/// ```
+#[derive(Debug, Clone)]
+pub struct CompactOptions {
+    maskbit: i32,
+    cmaskbit: i32,
+    maxelementcnt: i32,
+    pagesize: usize,
+}
+
+impl Default for CompactOptions {
+    fn default() -> Self {
+        CompactOptions {
+            maskbit: 8,
+            cmaskbit: 4,
+            maxelementcnt: 1024,
+            pagesize: 512,
+        }
+    }
+}
+
+impl CompactOptions {
+    /// Create a new CompactOptions with the parameters of `K2hash::open`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the mask bit count.
+    pub fn maskbit(mut self, maskbit: i32) -> Self {
+        self.maskbit = maskbit;
+        self
+    }
+
+    /// Set the collision mask bit count.
+    pub fn cmaskbit(mut self, cmaskbit: i32) -> Self {
+        self.cmaskbit = cmaskbit;
+        self
+    }
+
+    /// Set the max element count.
+    pub fn maxelementcnt(mut self, maxelementcnt: i32) -> Self {
+        self.maxelementcnt = maxelementcnt;
+        self
+    }
+
+    /// Set the page size.
+    pub fn pagesize(mut self, pagesize: usize) -> Self {
+        self.pagesize = pagesize;
+        self
+    }
+}
+
+/// CompactReport is the result of `K2hash::compact_into` and `K2hash::compact`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CompactReport {
+    /// The number of copied keys.
+    pub keys: usize,
+    /// The file size before compaction, which is 0 for a memory-based database.
+    pub old_size: u64,
+    /// The file size after compaction.
+    pub new_size: u64,
+}
+
+impl CompactReport {
+    /// Get the reclaimed bytes.
+    pub fn reclaimed(&self) -> u64 {
+        self.old_size.saturating_sub(self.new_size)
+    }
+}
+
+/// HandleSettings records the per-handle settings of a K2hash so `K2hash::compact` can apply
+/// them to the reopened handle.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct HandleSettings {
+    pub(crate) mtime: Option<bool>,
+    pub(crate) encryption: Option<bool>,
+    pub(crate) history: Option<bool>,
+    /// The passwords in the order they were added, with the default flag.
+    pub(crate) passwords: Vec<(String, bool)>,
+    pub(crate) plugins: Vec<String>,
+}
+
+impl HandleSettings {
+    /// Applies the settings to the handle in the order a caller would.
+    fn apply_to(&self, db: &K2hash) -> Result<(), &'static str> {
+        for plugin in &self.plugins {
+            db.add_attribute_plugin_lib(plugin)?;
+        }
+        for (password, is_default) in &self.passwords {
+            if *is_default {
+                db.set_default_encryption_password(password)?;
+            } else {
+                db.add_decryption_password(password)?;
+            }
+        }
+        if let Some(enable) = self.mtime {
+            db.enable_mtime(enable)?;
+        }
+        if let Some(enable) = self.encryption {
+            db.enable_encryption(enable)?;
+        }
+        if let Some(enable) = self.history {
+            db.enable_history(enable)?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the size of a file, or 0 if it does not exist.
+fn file_size(path: &str) -> u64 {
+    fs::metadata(path).map_or(0, |m| m.len())
+}
+
+impl K2hash {
+    /// Rebuild the database into a new file with the table parameters.
+    ///
+    /// The database should not be changed while compacting, otherwise the changes may be lost.
+    pub fn compact_into(
+        &self,
+        new_path: &str,
+        options: CompactOptions,
+    ) -> Result<CompactReport, &'static str> {
+        if Path::new(new_path).exists() {
+            return Err("new_path already exists");
+        }
+        let backup = self.backup_to(
+            new_path,
+            BackupOptions::new().create_params(
+                options.maskbit,
+                options.cmaskbit,
+                options.maxelementcnt,
+                options.pagesize,
+            ),
+        )?;
+        Ok(CompactReport {
+            keys: backup.keys,
+            old_size: self.path.as_deref().map_or(0, file_size),
+            new_size: file_size(new_path),
+        })
+    }
+
+    /// Rebuild the file-backed database with the table parameters and swap the files.
+    ///
+    /// The compacted file replaces the old one by an atomic rename and this handle is reopened
+    /// on it. Other handles and queues made from the old handle keep using the old file, so they
+    /// should be closed before and opened again after compacting.
+    ///
+    /// The reopened handle keeps the TxListener, the attribute plugin libraries, the encryption
+    /// passwords and the mtime, encryption and history settings made through this handle.
+    /// Settings made directly through the C-library on `handle()` are not carried over. A
+    /// database with a transaction enabled is refused because the transaction can not move
+    /// to the new handle.
+    pub fn compact(&mut self, options: CompactOptions) -> Result<CompactReport, &'static str> {
+        let path = self
+            .path
+            .clone()
+            .ok_or("compact needs a file-backed database")?;
+        if self.get_tx_file_fd().is_ok() {
+            return Err("compact needs a database without a transaction enabled");
+        }
+        let new_path = format!("{}.compact", path);
+        let _ = fs::remove_file(&new_path);
+        let report = match self.compact_into(&new_path, options) {
+            Ok(report) => report,
+            Err(e) => {
+                let _ = fs::remove_file(&new_path);
+                return Err(e);
+            }
+        };
+        fs::rename(&new_path, &path).map_err(|_| "Failed to swap the compacted file")?;
+        let db = K2hash::open(&path)?;
+        let settings = self.settings.lock().unwrap().clone();
+        settings.apply_to(&db)?;
+        let listener = self.listener.write().unwrap().take();
+        *db.listener.write().unwrap() = listener;
+        // the old handle is closed when it is dropped.
+        *self = db;
+        Ok(report)
+    }
+}
+
+//
+// Local variables:
+// tab-width: 4
+// c-basic-offset: 4
+// End:
+// vim600: expandtab sw=4 ts=4 fdm=marker
+// vim<600: expandtab sw=4 ts=4
+//
@@ -0,0 +1,185 @@
+//
+// k2hash_rust
+//
+// Copyright 2025 LY Corporation.
+//
+// Rust driver for k2hash that is a NoSQL Key Value Store(KVS) library.
+// For k2hash, see https://github.com/yahoojapan/k2hash for the details.
+//
+// For the full copyright and license information, please view
+// the license file that was distributed with this source code.
+//
+// AUTHOR:   Hirotaka Wakabayashi
+// CREATE:   Fri, 17 Jul 2025
+// REVISION:
+//
+
+//! Encryption status of keys and password rotation.
+
+use std::ffi::{c_void, CString};
+use std::os::raw::c_ulonglong;
+use std::ptr;
+use std::time::SystemTime;
+
+use crate::metadata::{attribute_name, decode_time, is_encrypted, EXPIRE_ATTR};
+use crate::{
+    k2h_get_direct_value_wp, k2h_set_value_wa, read_attributes, read_attributes_raw,
+    read_value_raw, K2hash, RawKeys,
+};
+
+/// RotationFailure is a key which `K2hash::rotate_password` could not rotate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RotationFailure {
+    /// The key without the terminating null.
+    pub key: String,
+    /// The reason of the failure.
+    pub reason: &'static str,
+}
+
+/// RotationReport is the progress and the result of `K2hash::rotate_password`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RotationReport {
+    /// The number of keys which passed the filter.
+    pub checked: usize,
+    /// The number of keys which are encrypted with the new password now.
+    pub rotated: usize,
+    /// The number of keys which are not encrypted.
+    pub skipped: usize,
+    /// The keys which could not be rotated.
+    pub failures: Vec<RotationFailure>,
+}
+
+/// Reads a value of a key in binary with a password.
+fn read_value_with_pass(k2h: u64, key: &[u8], pass: &CString) -> Option<Vec<u8>> {
+    let mut length: usize = 0;
+    let ptr = unsafe {
+        k2h_get_direct_value_wp(k2h, key.as_ptr(), key.len(), &mut length, pass.as_ptr())
+    };
+    if ptr.is_null() {
+        return None;
+    }
+    let val = unsafe { std::slice::from_raw_parts(ptr, length).to_vec() };
+    unsafe { libc::free(ptr as *mut c_void) };
+    Some(val)
+}
+
+/// Returns the seconds left until the expire attribute, which is at least one second.
+fn remaining_expire(attrs: &[(Vec<u8>, Vec<u8>)]) -> Option<c_ulonglong> {
+    let (_, val) = attrs
+        .iter()
+        .find(|(name, _)| attribute_name(name) == EXPIRE_ATTR)?;
+    let left = decode_time(val)?
+        .duration_since(SystemTime::now())
+        .unwrap_or_default();
+    Some(left.as_secs().max(1) as c_ulonglong)
+}
+
+/// Re-encrypts a value of a key from the old password to the new one.
+///
+/// The value is written again with the seconds left until its expiration, so the key expires
+/// at the same time as before.
+fn rotate_key(k2h: u64, key: &[u8], old: &CString, new: &CString) -> Result<(), &'static str> {
+    let value =
+        read_value_with_pass(k2h, key, old).ok_or("decryption with the old password failed")?;
+    let expire = remaining_expire(&read_attributes_raw(k2h, key));
+    let result = unsafe {
+        k2h_set_value_wa(
+            k2h,
+            key.as_ptr(),
+            key.len(),
+            value.as_ptr(),
+            value.len(),
+            new.as_ptr(),
+            expire
+                .as_ref()
+                .map_or(ptr::null(), |e| e as *const c_ulonglong),
+        )
+    };
+    if !result {
+        return Err("k2h_set_value_wa returns error");
+    }
+    if read_value_with_pass(k2h, key, new).as_deref() != Some(value.as_slice()) {
+        return Err("verification with the new password failed");
+    }
+    Ok(())
+}
+
+impl K2hash {
+    /// Check if the value of a key is encrypted.
+    ///
+    /// Returns an error if the key does not exist.
+    pub fn is_encrypted(&self, key: &str) -> Result<bool, &'static str> {
+        if key.is_empty() {
+            return Err("key should be passed");
+        }
+        let attrs = read_attributes(self.handle, key);
+        // an encrypted value always has attributes, so only a key without them is looked up.
+        if attrs.is_empty() {
+            let k = CString::new(key).unwrap();
+            if read_value_raw(self.handle, k.as_bytes_with_nul()).is_none() {
+                return Err("key does not exist");
+            }
+        }
+        Ok(is_encrypted(&attrs))
+    }
+
+    /// Re-encrypt the encrypted values of the keys passing `filter` from the old password to the
+    /// new one.
+    ///
+    /// Keys which are not encrypted are skipped. A key which fails is recorded in the report
+    /// and the rotation goes on with the next key. Register the new password with
+    /// `add_decryption_password` to read the rotated values without passing it.
+    pub fn rotate_password<F: Fn(&str) -> bool>(
+        &self,
+        old: &str,
+        new: &str,
+        filter: F,
+    ) -> Result<RotationReport, &'static str> {
+        self.rotate_password_with_progress(old, new, filter, |_| {})
+    }
+
+    /// Re-encrypt the encrypted values like `rotate_password`, calling `progress` after each key.
+    pub fn rotate_password_with_progress<F: Fn(&str) -> bool, P: FnMut(&RotationReport)>(
+        &self,
+        old: &str,
+        new: &str,
+        filter: F,
+        mut progress: P,
+    ) -> Result<RotationReport, &'static str> {
+        if old.is_empty() || new.is_empty() {
+            return Err("passwords should be passed");
+        }
+        let old = CString::new(old).unwrap();
+        let new = CString::new(new).unwrap();
+        // collect the keys first because rewriting values while walking them is not safe.
+        let keys: Vec<Vec<u8>> = RawKeys::new(self.handle).collect();
+        let mut report = RotationReport::default();
+        for key in keys {
+            let name = key.strip_suffix(&[0]).unwrap_or(&key);
+            let name = String::from_utf8_lossy(name).into_owned();
+            if !filter(&name) {
+                continue;
+            }
+            report.checked += 1;
+            if !is_encrypted(&read_attributes_raw(self.handle, &key)) {
+                report.skipped += 1;
+            } else {
+                match rotate_key(self.handle, &key, &old, &new) {
+                    Ok(()) => report.rotated += 1,
+                    Err(reason) => report.failures.push(RotationFailure { key: name, reason }),
+                }
+            }
+            progress(&report);
+        }
+        Ok(report)
+    }
+}
+
+//
+// Local variables:
+// tab-width: 4
+// c-basic-offset: 4
+// End:
+// vim600: expandtab sw=4 ts=4 fdm=marker
+// vim<600: expandtab sw=4 ts=4
+//
@@ -0,0 +1,303 @@
+//
+// k2hash_rust
+//
+// Copyright 2025 LY Corporation.
+//
+// Rust driver for k2hash that is a NoSQL Key Value Store(KVS) library.
+// For k2hash, see https://github.com/yahoojapan/k2hash for the details.
+//
+// For the full copyright and license information, please view
+// the license file that was distributed with this source code.
+//
+// AUTHOR:   Hirotaka Wakabayashi
+// CREATE:   Fri, 17 Jul 2025
+// REVISION:
+//
+
+//! JSON, NDJSON and CSV export and import of the contents of a K2hash database.
+//!
+//! Keys, values, subkeys and attribute names and values are written as strings when they
+//! are text with the terminating null, which is stripped. Other bytes are written in
+//! base64, as `{"base64": "..."}` in JSON and as `base64:...` in a CSV cell.
+//!
+//! A JSON record is an object with `key`, `value`, `subkeys` and `attributes`, where `value`
+//! is null if the key has no value and `attributes` is an array of name and value pairs. A
+//! CSV row has the same columns, and the subkeys and attributes cells hold the JSON arrays.
+
+use std::io::{BufRead, BufReader, Read, Write};
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde_json::{json, Value};
+
+use crate::txlog::RawAttributes;
+use crate::{read_attributes_raw, read_subkeys_raw, read_value_raw, set_all_raw, K2hash, RawKeys};
+
+/// The prefix of a base64 CSV cell.
+const BASE64_CELL: &str = "base64:";
+/// The header of a CSV export.
+const CSV_HEADER: [&str; 4] = ["key", "value", "subkeys", "attributes"];
+
+/// A key with its value, subkeys and attributes in binary.
+struct Entry {
+    key: Vec<u8>,
+    value: Option<Vec<u8>>,
+    subkeys: Vec<Vec<u8>>,
+    attributes: RawAttributes,
+}
+
+impl Entry {
+    fn read(k2h: u64, key: Vec<u8>) -> Self {
+        Entry {
+            value: read_value_raw(k2h, &key),
+            subkeys: read_subkeys_raw(k2h, &key),
+            attributes: read_attributes_raw(k2h, &key),
+            key,
+        }
+    }
+
+    fn write(&self, k2h: u64) -> Result<(), &'static str> {
+        let value = self.value.as_deref().unwrap_or_default();
+        if set_all_raw(k2h, &self.key, value, &self.subkeys, &self.attributes) {
+            Ok(())
+        } else {
+            Err("k2h_set_all returns error")
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "key": bytes_to_json(&self.key),
+            "value": self.value.as_deref().map_or(Value::Null, bytes_to_json),
+            "subkeys": subkeys_to_json(&self.subkeys),
+            "attributes": attributes_to_json(&self.attributes),
+        })
+    }
+
+    fn from_json(record: &Value) -> Result<Self, &'static str> {
+        let key = json_to_bytes(record.get("key").ok_or("record has no key")?)?;
+        let value = match record.get("value") {
+            None | Some(Value::Null) => None,
+            Some(v) => Some(json_to_bytes(v)?),
+        };
+        let subkeys = match record.get("subkeys") {
+            None | Some(Value::Null) => Vec::new(),
+            Some(v) => json_to_subkeys(v)?,
+        };
+        let attributes = match record.get("attributes") {
+            None | Some(Value::Null) => Vec::new(),
+            Some(v) => json_to_attributes(v)?,
+        };
+        Ok(Entry {
+            key,
+            value,
+            subkeys,
+            attributes,
+        })
+    }
+
+    fn to_csv(&self) -> [String; 4] {
+        [
+            bytes_to_cell(&self.key),
+            self.value.as_deref().map_or(String::new(), bytes_to_cell),
+            subkeys_to_json(&self.subkeys).to_string(),
+            attributes_to_json(&self.attributes).to_string(),
+        ]
+    }
+
+    fn from_csv(row: &csv::StringRecord) -> Result<Self, &'static str> {
+        let cell = |i: usize| row.get(i).unwrap_or_default();
+        let key = cell_to_bytes(cell(0))?;
+        let value = match cell(1) {
+            "" => None,
+            v => Some(cell_to_bytes(v)?),
+        };
+        let subkeys = match cell(2) {
+            "" => Vec::new(),
+            v => json_to_subkeys(&serde_json::from_str(v).map_err(|_| "broken subkeys")?)?,
+        };
+        let attributes = match cell(3) {
+            "" => Vec::new(),
+            v => json_to_attributes(&serde_json::from_str(v).map_err(|_| "broken attributes")?)?,
+        };
+        Ok(Entry {
+            key,
+            value,
+            subkeys,
+            attributes,
+        })
+    }
+}
+
+/// Returns the text of bytes written by the C-API, or None if they should be in base64.
+fn bytes_to_text(bytes: &[u8]) -> Option<&str> {
+    let text = bytes.strip_suffix(b"\0")?;
+    if text.contains(&0) {
+        return None;
+    }
+    std::str::from_utf8(text).ok()
+}
+
+/// Appends the terminating null to a text.
+fn text_to_bytes(text: &str) -> Vec<u8> {
+    let mut bytes = text.as_bytes().to_vec();
+    bytes.push(0);
+    bytes
+}
+
+fn bytes_to_json(bytes: &[u8]) -> Value {
+    match bytes_to_text(bytes) {
+        Some(text) => Value::String(text.to_string()),
+        None => json!({ "base64": STANDARD.encode(bytes) }),
+    }
+}
+
+fn json_to_bytes(value: &Value) -> Result<Vec<u8>, &'static str> {
+    match value {
+        Value::String(text) => Ok(text_to_bytes(text)),
+        Value::Object(obj) => match obj.get("base64") {
+            Some(Value::String(encoded)) => STANDARD.decode(encoded).map_err(|_| "broken base64"),
+            _ => Err("object should have a base64 string"),
+        },
+        _ => Err("bytes should be a string or a base64 object"),
+    }
+}
+
+fn bytes_to_cell(bytes: &[u8]) -> String {
+    match bytes_to_text(bytes) {
+        Some(text) if !text.starts_with(BASE64_CELL) => text.to_string(),
+        _ => format!("{}{}", BASE64_CELL, STANDARD.encode(bytes)),
+    }
+}
+
+fn cell_to_bytes(cell: &str) -> Result<Vec<u8>, &'static str> {
+    match cell.strip_prefix(BASE64_CELL) {
+        Some(encoded) => STANDARD.decode(encoded).map_err(|_| "broken base64"),
+        None => Ok(text_to_bytes(cell)),
+    }
+}
+
+fn subkeys_to_json(subkeys: &[Vec<u8>]) -> Value {
+    Value::Array(subkeys.iter().map(|k| bytes_to_json(k)).collect())
+}
+
+fn json_to_subkeys(value: &Value) -> Result<Vec<Vec<u8>>, &'static str> {
+    value
+        .as_array()
+        .ok_or("subkeys should be an array")?
+        .iter()
+        .map(json_to_bytes)
+        .collect()
+}
+
+fn attributes_to_json(attrs: &[(Vec<u8>, Vec<u8>)]) -> Value {
+    Value::Array(
+        attrs
+            .iter()
+            .map(|(name, val)| json!([bytes_to_json(name), bytes_to_json(val)]))
+            .collect(),
+    )
+}
+
+fn json_to_attributes(value: &Value) -> Result<RawAttributes, &'static str> {
+    value
+        .as_array()
+        .ok_or("attributes should be an array")?
+        .iter()
+        .map(|pair| match pair.as_array().map(|p| p.as_slice()) {
+            Some([name, val]) => Ok((json_to_bytes(name)?, json_to_bytes(val)?)),
+            _ => Err("attribute should be a pair of name and value"),
+        })
+        .collect()
+}
+
+impl K2hash {
+    /// Export all keys as a JSON array and return the number of exported keys.
+    pub fn export_json<W: Write>(&self, writer: &mut W) -> Result<usize, &'static str> {
+        let mut count = 0;
+        writer.write_all(b"[").map_err(|_| "Failed to write")?;
+        for key in RawKeys::new(self.handle) {
+            let sep: &[u8] = if count == 0 { b"\n" } else { b",\n" };
+            writer.write_all(sep).map_err(|_| "Failed to write")?;
+            serde_json::to_writer(&mut *writer, &Entry::read(self.handle, key).to_json())
+                .map_err(|_| "Failed to write")?;
+            count += 1;
+        }
+        writer.write_all(b"\n]\n").map_err(|_| "Failed to write")?;
+        Ok(count)
+    }
+
+    /// Export all keys as newline delimited JSON and return the number of exported keys.
+    pub fn export_ndjson<W: Write>(&self, writer: &mut W) -> Result<usize, &'static str> {
+        let mut count = 0;
+        for key in RawKeys::new(self.handle) {
+            serde_json::to_writer(&mut *writer, &Entry::read(self.handle, key).to_json())
+                .map_err(|_| "Failed to write")?;
+            writer.write_all(b"\n").map_err(|_| "Failed to write")?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// Export all keys as CSV with a header row and return the number of exported keys.
+    pub fn export_csv<W: Write>(&self, writer: &mut W) -> Result<usize, &'static str> {
+        let mut csv_writer = csv::Writer::from_writer(writer);
+        csv_writer
+            .write_record(CSV_HEADER)
+            .map_err(|_| "Failed to write")?;
+        let mut count = 0;
+        for key in RawKeys::new(self.handle) {
+            csv_writer
+                .write_record(Entry::read(self.handle, key).to_csv())
+                .map_err(|_| "Failed to write")?;
+            count += 1;
+        }
+        csv_writer.flush().map_err(|_| "Failed to write")?;
+        Ok(count)
+    }
+
+    /// Import keys from a JSON array written by `export_json` and return the number of keys.
+    pub fn import_json<R: Read>(&self, reader: &mut R) -> Result<usize, &'static str> {
+        let records: Vec<Value> = serde_json::from_reader(reader).map_err(|_| "broken JSON")?;
+        for record in records.iter() {
+            Entry::from_json(record)?.write(self.handle)?;
+        }
+        Ok(records.len())
+    }
+
+    /// Import keys from newline delimited JSON written by `export_ndjson` and return the count.
+    pub fn import_ndjson<R: Read>(&self, reader: &mut R) -> Result<usize, &'static str> {
+        let mut count = 0;
+        for line in BufReader::new(reader).lines() {
+            let line = line.map_err(|_| "Failed to read")?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let record: Value = serde_json::from_str(&line).map_err(|_| "broken JSON")?;
+            Entry::from_json(&record)?.write(self.handle)?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// Import keys from CSV written by `export_csv` and return the number of keys.
+    pub fn import_csv<R: Read>(&self, reader: &mut R) -> Result<usize, &'static str> {
+        let mut csv_reader = csv::Reader::from_reader(reader);
+        let mut count = 0;
+        for row in csv_reader.records() {
+            let row = row.map_err(|_| "broken CSV")?;
+            Entry::from_csv(&row)?.write(self.handle)?;
+            count += 1;
+        }
+        Ok(count)
+    }
+}
+
+//
+// Local variables:
+// tab-width: 4
+// c-basic-offset: 4
+// End:
+// vim600: expandtab sw=4 ts=4 fdm=marker
+// vim<600: expandtab sw=4 ts=4
+//
@@ -0,0 +1,147 @@
+//
+// k2hash_rust
+//
+// Copyright 2025 LY Corporation.
+//
+// Rust driver for k2hash that is a NoSQL Key Value Store(KVS) library.
+// For k2hash, see https://github.com/yahoojapan/k2hash for the details.
+//
+// For the full copyright and license information, please view
+// the license file that was distributed with this source code.
+//
+// AUTHOR:   Hirotaka Wakabayashi
+// CREATE:   Fri, 17 Jul 2025
+// REVISION:
+//
+
+//! Access to the previous values of a key recorded by `K2hash::enable_history`.
+
+use std::collections::HashSet;
+use std::ffi::CString;
+use std::time::SystemTime;
+
+use crate::metadata::{
+    attribute_name, decode_history, decode_timespec, is_encrypted, HISTORY_ATTR, MTIME_ATTR,
+};
+use crate::{instrument, k2h_set_value, read_attributes_raw, read_value_raw, K2hash};
+
+/// HistoryEntry is a previous value of a key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistoryEntry {
+    /// The key which holds the previous value.
+    pub key: Vec<u8>,
+    /// The previous value in binary.
+    pub value: Vec<u8>,
+    /// The modification time of the previous value, recorded when `K2hash::enable_mtime` is enabled.
+    pub mtime: Option<SystemTime>,
+}
+
+impl HistoryEntry {
+    /// Get the value as a string without the terminating null.
+    pub fn value_str(&self) -> String {
+        let value = self.value.strip_suffix(&[0]).unwrap_or(&self.value);
+        String::from_utf8_lossy(value).into_owned()
+    }
+}
+
+/// Returns the history keys and the mtime recorded in the attributes of a key.
+fn history_of(k2h: u64, key: &[u8]) -> (Vec<Vec<u8>>, Option<SystemTime>) {
+    let mut history_keys = Vec::new();
+    let mut mtime = None;
+    for (name, val) in read_attributes_raw(k2h, key) {
+        match attribute_name(&name) {
+            HISTORY_ATTR => history_keys = decode_history(&val),
+            MTIME_ATTR => mtime = decode_timespec(&val),
+            _ => {}
+        }
+    }
+    (history_keys, mtime)
+}
+
+impl K2hash {
+    /// Get the previous values of a key, the newest first.
+    ///
+    /// The history attribute of the key and of each previous value is followed, so the whole
+    /// chain is returned. Entries without an mtime come last.
+    pub fn history(&self, key: &str) -> Result<Vec<HistoryEntry>, &'static str> {
+        let k = CString::new(key).unwrap();
+        if k.is_empty() {
+            return Err("key should be passed");
+        }
+        let (mut pending, _) = history_of(self.handle, k.as_bytes_with_nul());
+        pending.reverse();
+        let mut visited = HashSet::new();
+        let mut entries = Vec::new();
+        while let Some(history_key) = pending.pop() {
+            if !visited.insert(history_key.clone()) {
+                continue;
+            }
+            let value = match read_value_raw(self.handle, &history_key) {
+                Some(value) => value,
+                None => continue, // the previous value has been removed
+            };
+            let (older, mtime) = history_of(self.handle, &history_key);
+            pending.extend(older.into_iter().rev());
+            entries.push(HistoryEntry {
+                key: history_key,
+                value,
+                mtime,
+            });
+        }
+        // newest first, keeping the chain order for the same or missing mtimes.
+        entries.sort_by(|a, b| match (a.mtime, b.mtime) {
+            (Some(a), Some(b)) => b.cmp(&a),
+            (Some(_), None) => std::cmp::Ordering::Less,
+            (None, Some(_)) => std::cmp::Ordering::Greater,
+            (None, None) => std::cmp::Ordering::Equal,
+        });
+        Ok(entries)
+    }
+
+    /// Get the `n`-th previous value of a key, where 0 is the newest one.
+    pub fn get_version(&self, key: &str, n: usize) -> Result<Option<HistoryEntry>, &'static str> {
+        Ok(self.history(key)?.into_iter().nth(n))
+    }
+
+    /// Set the `n`-th previous value of a key back to the key.
+    ///
+    /// The current value becomes the newest previous value while the history is enabled, so a
+    /// restore can be undone by restoring version 0.
+    ///
+    /// An encrypted previous value is refused, because it is written back without a password
+    /// and the key would hold the encrypted bytes as a plain value.
+    pub fn restore_version(&self, key: &str, n: usize) -> Result<(), &'static str> {
+        let entry = self.get_version(key, n)?.ok_or("version does not exist")?;
+        if is_encrypted(&read_attributes_raw(self.handle, &entry.key)) {
+            return Err("an encrypted version can not be restored");
+        }
+        instrument::observe("restore_version", Some(key.len()), || {
+            let k = CString::new(key).unwrap();
+            let k = k.as_bytes_with_nul();
+            let result = unsafe {
+                k2h_set_value(
+                    self.handle,
+                    k.as_ptr(),
+                    k.len(),
+                    entry.value.as_ptr(),
+                    entry.value.len(),
+                )
+            };
+            if result {
+                self.notify(|l| l.on_set(key, &entry.value_str()));
+                Ok(())
+            } else {
+                Err("k2h_set_value returns error")
+            }
+        })
+    }
+}
+
+//
+// Local variables:
+// tab-width: 4
+// c-basic-offset: 4
+// End:
+// vim600: expandtab sw=4 ts=4 fdm=marker
+// vim<600: expandtab sw=4 ts=4
+//
@@ -0,0 +1,217 @@
+//
+// k2hash_rust
+//
+// Copyright 2025 LY Corporation.
+//
+// Rust driver for k2hash that is a NoSQL Key Value Store(KVS) library.
+// For k2hash, see https://github.com/yahoojapan/k2hash for the details.
+//
+// For the full copyright and license information, please view
+// the license file that was distributed with this source code.
+//
+// AUTHOR:   Hirotaka Wakabayashi
+// CREATE:   Fri, 17 Jul 2025
+// REVISION:
+//
+
+//! Instrumentation of K2hash operations.
+//!
+//! The data operations of K2hash, Queue and KeyQueue are observed, such as `set`, `rename`,
+//! the attribute updates, the queue pushes, pops and removes, the transaction calls and the
+//! dumps. Handle settings and debug controls are not. The README lists every `op` label.
+//!
+//! With the `metrics` feature, every observed operation is reported through the `metrics` facade:
+//!
+//! - `k2hash_operations_total` counter labeled by `op`
+//! - `k2hash_errors_total` counter labeled by `op`
+//! - `k2hash_operation_duration_seconds` histogram labeled by `op`
+//! - `k2hash_queue_depth` gauge labeled by `prefix`, sampled at most once a second per handle
+//!
+//! With the `tracing` feature, every observed operation runs in a `k2hash` debug span with the
+//! `op`, `key_len`, `outcome` and `elapsed_us` fields, and `K2hash::forward_debug_log` turns the
+//! debug output of the k2hash C-library into `tracing` events.
+//!
+//! Without the features, these helpers compile down to the operation itself.
+
+#[cfg(feature = "tracing")]
+use crate::K2hash;
+#[cfg(feature = "tracing")]
+use std::ffi::CString;
+#[cfg(feature = "tracing")]
+use std::fs::File;
+#[cfg(feature = "tracing")]
+use std::io::{BufRead, BufReader};
+#[cfg(feature = "tracing")]
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+#[cfg(any(feature = "metrics", feature = "tracing"))]
+use std::sync::Mutex;
+#[cfg(feature = "tracing")]
+use std::thread;
+#[cfg(feature = "metrics")]
+use std::time::Duration;
+#[cfg(any(feature = "metrics", feature = "tracing"))]
+use std::time::Instant;
+
+/// Outcome tells whether the result of an operation is an error.
+pub(crate) trait Outcome {
+    #[cfg_attr(not(any(feature = "metrics", feature = "tracing")), allow(dead_code))]
+    fn is_error(&self) -> bool;
+}
+
+impl<T> Outcome for Result<T, &'static str> {
+    fn is_error(&self) -> bool {
+        self.is_err()
+    }
+}
+
+/// `None` means an empty queue, which is not an error.
+impl<T> Outcome for Option<T> {
+    fn is_error(&self) -> bool {
+        false
+    }
+}
+
+/// Runs an operation and records its count, error count and latency.
+///
+/// `key_len` is the length of the key the operation works on, if any.
+#[cfg_attr(
+    not(all(feature = "metrics", feature = "tracing")),
+    allow(unused_variables)
+)]
+pub(crate) fn observe<T: Outcome, F: FnOnce() -> T>(
+    op: &'static str,
+    key_len: Option<usize>,
+    f: F,
+) -> T {
+    #[cfg(feature = "tracing")]
+    let span = tracing::debug_span!(
+        target: "k2hash",
+        "k2hash",
+        op,
+        key_len,
+        outcome = tracing::field::Empty,
+        elapsed_us = tracing::field::Empty,
+    );
+    #[cfg(feature = "tracing")]
+    let _enter = span.enter();
+    #[cfg(any(feature = "metrics", feature = "tracing"))]
+    let start = Instant::now();
+    let result = f();
+    #[cfg(feature = "tracing")]
+    {
+        span.record("outcome", if result.is_error() { "error" } else { "ok" });
+        span.record("elapsed_us", start.elapsed().as_micros() as u64);
+    }
+    #[cfg(feature = "metrics")]
+    {
+        metrics::histogram!("k2hash_operation_duration_seconds", "op" => op)
+            .record(start.elapsed().as_secs_f64());
+        metrics::counter!("k2hash_operations_total", "op" => op).increment(1);
+        if result.is_error() {
+            metrics::counter!("k2hash_errors_total", "op" => op).increment(1);
+        }
+    }
+    result
+}
+
+/// The interval between two samples of a queue depth.
+#[cfg(feature = "metrics")]
+const DEPTH_SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// DepthSampler records the depth of a queue at most once per `DEPTH_SAMPLE_INTERVAL`.
+///
+/// Counting the elements is a library call of its own, so doing it on every push and pop
+/// would double their cost. Other handles and processes change the depth too, so it is
+/// sampled from the library rather than tracked by a counter.
+#[derive(Default)]
+pub(crate) struct DepthSampler {
+    #[cfg(feature = "metrics")]
+    last: Mutex<Option<Instant>>,
+}
+
+impl DepthSampler {
+    /// Records the depth of a queue. `depth` is called only when a sample is due.
+    #[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+    pub(crate) fn record<F: FnOnce() -> usize>(&self, prefix: Option<&str>, depth: F) {
+        #[cfg(feature = "metrics")]
+        {
+            let mut last = self.last.lock().unwrap();
+            if last.is_some_and(|at| at.elapsed() < DEPTH_SAMPLE_INTERVAL) {
+                return;
+            }
+            *last = Some(Instant::now());
+            metrics::gauge!("k2hash_queue_depth", "prefix" => prefix.unwrap_or("").to_string())
+                .set(depth() as f64);
+        }
+    }
+}
+
+/// The write end of the pipe whose lines are forwarded by `K2hash::forward_debug_log`.
+#[cfg(feature = "tracing")]
+static DEBUG_PIPE: Mutex<Option<OwnedFd>> = Mutex::new(None);
+
+#[cfg(feature = "tracing")]
+impl K2hash {
+    /// Forward the debug output of the k2hash C-library to `tracing` events.
+    ///
+    /// The output is redirected to a pipe by `k2h_set_debug_file` and a background thread emits
+    /// each line with the `k2hash` target. Lines prefixed by `[ERR]`, `[WAN]` and `[MSG]` become
+    /// error, warn and info events, the others debug events. Use `set_debug_level` to choose
+    /// which messages the C-library writes.
+    ///
+    /// The pipe and the thread are created by the first call and live as long as the process.
+    /// A later call, such as one after `set_debug_file`, only redirects the output to the pipe
+    /// again.
+    pub fn forward_debug_log() -> Result<(), &'static str> {
+        let mut pipe = DEBUG_PIPE.lock().unwrap();
+        if pipe.is_none() {
+            let mut fds = [0; 2];
+            if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) } != 0 {
+                return Err("Failed to create a pipe");
+            }
+            let reader = unsafe { File::from_raw_fd(fds[0]) };
+            let writer = unsafe { OwnedFd::from_raw_fd(fds[1]) };
+            thread::Builder::new()
+                .name("k2hash-debug".to_string())
+                .spawn(move || {
+                    for line in BufReader::new(reader).lines().map_while(Result::ok) {
+                        emit_debug_line(&line);
+                    }
+                })
+                .map_err(|_| "Failed to spawn the debug log thread")?;
+            *pipe = Some(writer);
+        }
+        let fd = pipe.as_ref().unwrap().as_raw_fd();
+        // the C-library opens its own descriptor of the pipe.
+        let path = CString::new(format!("/dev/fd/{}", fd)).unwrap();
+        if unsafe { crate::k2h_set_debug_file(path.as_ptr()) } {
+            Ok(())
+        } else {
+            Err("k2h_set_debug_file returns error")
+        }
+    }
+}
+
+/// Emits a line of the k2hash debug output at the level of its prefix.
+#[cfg(feature = "tracing")]
+fn emit_debug_line(line: &str) {
+    let (level, message) = match line.split_once("] ") {
+        Some((level, message)) => (level.trim_start_matches('['), message),
+        None => ("", line),
+    };
+    match level {
+        "ERR" => tracing::error!(target: "k2hash", "{}", message),
+        "WAN" => tracing::warn!(target: "k2hash", "{}", message),
+        "MSG" => tracing::info!(target: "k2hash", "{}", message),
+        _ => tracing::debug!(target: "k2hash", "{}", line),
+    }
+}
+
+//
+// Local variables:
+// tab-width: 4
+// c-basic-offset: 4
+// End:
+// vim600: expandtab sw=4 ts=4 fdm=marker
+// vim<600: expandtab sw=4 ts=4
+//
@@ -46,14 +46,18 @@ cargo add k2hash_rust
 Here is a simple example of **k2hash_rust** that saves a key and get it.
 
 ```rust
+# #[cfg(feature = "libk2hash")]
 use k2hash_rust::K2hash;
 
+# #[cfg(feature = "libk2hash")]
 fn main() {
     let db = K2hash::open_mem().expect("open_mem failed");
     db.set("foo", "bar");
     let v = db.get("foo");
     println!("foo => {:?}", v);
 }
+# #[cfg(not(feature = "libk2hash"))]
+# fn main() {}
 ```
 
 ## Development
@@ -88,17 +92,90 @@ MIT License. See the LICENSE file.
   - a product family of open source software developed by [AntPickax](https://antpick.ax/).
 */
 
+#[cfg(feature = "libk2hash")]
 use std::collections::HashMap; // Import HashMap for attributes
 
 // CString:    create CString instance from Rust string.
 // CStr:       create CStr instance from C API's pointer.
+#[cfg(feature = "libk2hash")]
 use std::ffi::{CStr, CString};
-use std::os::raw::{c_char, c_int, c_uchar, c_ulong, c_ulonglong, c_void};
+#[cfg(feature = "libk2hash")]
+use std::io::Write;
+#[cfg(feature = "libk2hash")]
+use std::os::fd::{BorrowedFd, FromRawFd, OwnedFd};
+#[cfg(feature = "libk2hash")]
+use std::os::raw::{c_char, c_int, c_long, c_uchar, c_ulong, c_ulonglong, c_void};
+#[cfg(feature = "libk2hash")]
 use std::ptr;
+#[cfg(feature = "libk2hash")]
+use std::sync::atomic::{AtomicU64, Ordering};
+#[cfg(feature = "libk2hash")]
+use std::sync::{Arc, Mutex, Once, OnceLock, RwLock};
+#[cfg(feature = "libk2hash")]
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[cfg(feature = "libk2hash")]
+mod archive;
+#[cfg(feature = "libk2hash")]
+pub use archive::{ArchiveReader, ArchiveSummary, ArchiveWriter, SkippedRecord};
+
+#[cfg(feature = "libk2hash")]
+mod backup;
+#[cfg(feature = "libk2hash")]
+pub use backup::{BackupOptions, BackupReport};
+
+#[cfg(feature = "libk2hash")]
+mod compact;
+#[cfg(feature = "libk2hash")]
+use compact::HandleSettings;
+#[cfg(feature = "libk2hash")]
+pub use compact::{CompactOptions, CompactReport};
+
+#[cfg(feature = "archive-parser")]
+mod archive_parser;
+#[cfg(feature = "archive-parser")]
+pub use archive_parser::{ArchiveEntry, ArchiveParser};
+
+#[cfg(feature = "libk2hash")]
+mod encryption;
+#[cfg(feature = "libk2hash")]
+pub use encryption::{RotationFailure, RotationReport};
+
+#[cfg(feature = "export")]
+mod export;
+
+#[cfg(feature = "libk2hash")]
+mod history;
+#[cfg(feature = "libk2hash")]
+pub use history::HistoryEntry;
+
+#[cfg(feature = "libk2hash")]
+mod instrument;
+
+mod metadata;
+pub use metadata::KeyMetadata;
+#[cfg(feature = "libk2hash")]
+use metadata::{attribute_name, is_builtin_attribute};
+
+mod txlog;
+#[cfg(feature = "libk2hash")]
+use txlog::{overwrite_value, read_checkpoint, write_checkpoint};
+pub use txlog::{ApplyTxOptions, ApplyTxReport, TxChange, TxLogReader, TxOperation, TxRecord};
+
+mod version;
+#[cfg(feature = "libk2hash")]
+use version::parse_version_banner;
+pub use version::{Version, MIN_LIBRARY_VERSION};
+
+#[cfg(feature = "tokio")]
+mod async_k2hash;
+#[cfg(feature = "tokio")]
+pub use async_k2hash::{AsyncK2hash, AsyncKeyQueue, AsyncQueue, QueueStream};
 
 // use libc::size_t;
 
 /// DumpLevel represents the level of detail in the dump output.
+#[cfg(feature = "libk2hash")]
 #[derive(PartialEq)]
 pub enum DumpLevel {
     HEADER,
@@ -109,6 +186,7 @@ pub enum DumpLevel {
 }
 
 /// DebugLevel represents the level of detail in the debug output.
+#[cfg(feature = "libk2hash")]
 pub enum DebugLevel {
     SILENT,
     ERROR,
@@ -126,6 +204,7 @@ pub enum DebugLevel {
 // #
 
 /// K2hKeyPack represents C-API's K2HKEYPCK structure.
+#[cfg(feature = "libk2hash")]
 #[repr(C)]
 pub struct K2hKeyPack {
     pub pkey: *mut u8,
@@ -139,6 +218,7 @@ pub struct K2hKeyPack {
 // } K2HATTRPCK;
 
 /// K2hAttrPack represents C-API's K2HATTRPCK structure.
+#[cfg(feature = "libk2hash")]
 #[repr(C)]
 pub struct K2hAttrPack {
     pub pkey: *mut u8,
@@ -146,9 +226,91 @@ pub struct K2hAttrPack {
     pub pval: *mut u8,
     pub vallength: usize,
 }
+// typedef struct k2h_state{
+//     char            version[K2H_VERSION_LENGTH];
+//     char            hash_version[K2H_HASH_FUNC_VER_LENGTH];
+//     char            trans_version[K2H_TRANS_FUNC_VER_LENGTH];
+//     int             trans_pool_count;
+//     k2h_hash_t      max_mask;
+//     k2h_hash_t      min_mask;
+//     k2h_hash_t      cur_mask;
+//     k2h_hash_t      collision_mask;
+//     unsigned long   max_element_count;
+//     size_t          total_size;
+//     size_t          page_size;
+//     size_t          file_size;
+//     size_t          total_used_size;
+//     size_t          total_map_size;
+//     size_t          total_element_size;
+//     size_t          total_page_size;
+//     long            total_area_count;
+//     long            total_element_count;
+//     long            total_page_count;
+//     long            assigned_area_count;
+//     long            assigned_key_count;
+//     long            assigned_ckey_count;
+//     long            assigned_element_count;
+//     long            assigned_page_count;
+//     long            unassigned_element_count;
+//     long            unassigned_page_count;
+//     struct timespec last_update;
+//     struct timespec last_area_update;
+// }K2HSTATE, *PK2HSTATE;
+
+/// K2H_VERSION_LENGTH of libk2hash lib/k2hash.h.
+#[cfg(feature = "libk2hash")]
+const K2H_VERSION_LENGTH: usize = 8;
+/// K2H_HASH_FUNC_VER_LENGTH of libk2hash lib/k2hash.h.
+#[cfg(feature = "libk2hash")]
+const K2H_HASH_FUNC_VER_LENGTH: usize = 32;
+/// K2H_TRANS_FUNC_VER_LENGTH of libk2hash lib/k2hash.h.
+#[cfg(feature = "libk2hash")]
+const K2H_TRANS_FUNC_VER_LENGTH: usize = 32;
+
+/// K2hState represents C-API's K2HSTATE structure of libk2hash lib/k2hash.h.
+///
+/// `k2h_hash_t` is `uint64_t`, and the `long` and `unsigned long` members follow the platform
+/// through the libc types.
+#[cfg(feature = "libk2hash")]
+#[repr(C)]
+struct K2hState {
+    version: [c_char; K2H_VERSION_LENGTH],
+    hash_version: [c_char; K2H_HASH_FUNC_VER_LENGTH],
+    trans_version: [c_char; K2H_TRANS_FUNC_VER_LENGTH],
+    trans_pool_count: c_int,
+    max_mask: u64,
+    min_mask: u64,
+    cur_mask: u64,
+    collision_mask: u64,
+    max_element_count: c_ulong,
+    total_size: usize,
+    page_size: usize,
+    file_size: usize,
+    total_used_size: usize,
+    total_map_size: usize,
+    total_element_size: usize,
+    total_page_size: usize,
+    total_area_count: c_long,
+    total_element_count: c_long,
+    total_page_count: c_long,
+    assigned_area_count: c_long,
+    assigned_key_count: c_long,
+    assigned_ckey_count: c_long,
+    assigned_element_count: c_long,
+    assigned_page_count: c_long,
+    unassigned_element_count: c_long,
+    unassigned_page_count: c_long,
+    last_update: libc::timespec,
+    last_area_update: libc::timespec,
+}
+
+// sizeof(K2HSTATE) is 288 bytes on LP64 platforms.
+#[cfg(all(feature = "libk2hash", target_pointer_width = "64"))]
+const _: () = assert!(std::mem::size_of::<K2hState>() == 288);
 
 // K2H_INVALID_HANDLE = 0;
 
+#[cfg(feature = "libk2hash")]
 #[link(name = "k2hash")]
 extern "C" {
 
@@ -465,6 +627,25 @@ extern "C" {
     /// * `bool` - true on success
     fn k2h_q_str_pop_wp(qhandle: u64, ppdata: *mut *mut c_char, encpass: *const c_char) -> bool;
 
+    /// k2h_q_str_pop_wa: Pop a value and its attributes from the queue with password
+    ///
+    /// # Arguments
+    /// * `qhandle` - queue handle
+    /// * `ppdata` - pointer to data pointer
+    /// * `ppattrspck` - pointer to attribute pack pointer
+    /// * `pattrspckcnt` - pointer to attribute pack count
+    /// * `encpass` - encryption password string
+    ///
+    /// # Returns
+    /// * `bool` - true on success
+    fn k2h_q_str_pop_wa(
+        qhandle: u64,
+        ppdata: *mut *mut c_char,
+        ppattrspck: *mut *mut c_void,
+        pattrspckcnt: *mut c_int,
+        encpass: *const c_char,
+    ) -> bool;
+
     /// k2h_q_dump: Dump queue contents to a stream
     ///
     /// # Arguments
@@ -764,6 +945,191 @@ extern "C" {
         pskeypckcnt: *mut c_int,
     ) -> *mut c_void;
 
+    /// # free attrs API
+    /// # bool k2h_free_attrpack(PK2HATTRPCK pattrs, int attrcnt)
+    /// k2h_free_attrpack: Free attribute pack
+    ///
+    /// # Arguments
+    /// * `pattrs` - pointer to attribute pack
+    /// * `attrcnt` - attribute count
+    ///
+    /// # Returns
+    /// * `bool` - true on success
+    fn k2h_free_attrpack(pattrs: *mut c_void, attrcnt: c_int) -> bool;
+
+    /// # bool k2h_free_keypack(PK2HKEYPCK pkeys, int keycnt)
+    /// k2h_free_keypack: Free key pack
+    ///
+    /// # Arguments
+    /// * `pkeys` - pointer to key pack
+    /// * `keycnt` - key count
+    ///
+    /// # Returns
+    /// * `bool` - true on success
+    fn k2h_free_keypack(pkeys: *mut c_void, keycnt: c_int) -> bool;
+
+    /// # unsigned char* k2h_get_direct_value(k2h_h handle, const unsigned char* pkey, size_t keylength, size_t* pvallength)
+    /// k2h_get_direct_value: Get a value in binary
+    ///
+    /// # Arguments
+    /// * `handle` - k2hash handle
+    /// * `pkey` - key pointer
+    /// * `keylength` - key length
+    /// * `pvallength` - pointer to value length
+    ///
+    /// # Returns
+    /// * `*mut u8` - pointer to the value which should be freed
+    fn k2h_get_direct_value(
+        handle: u64,
+        pkey: *const u8,
+        keylength: usize,
+        pvallength: *mut usize,
+    ) -> *mut u8;
+
+    /// # bool k2h_set_all(k2h_h handle, const unsigned char* pkey, size_t keylength, const unsigned char* pval, size_t vallength, const PK2HKEYPCK pskeypck, int skeypckcnt, const PK2HATTRPCK pattrspck, int attrspckcnt)
+    /// k2h_set_all: Set a value, subkeys and attributes in binary
+    ///
+    /// # Arguments
+    /// * `handle` - k2hash handle
+    /// * `pkey` - key pointer
+    /// * `keylength` - key length
+    /// * `pval` - value pointer
+    /// * `vallength` - value length
+    /// * `pskeypck` - pointer to subkey pack
+    /// * `skeypckcnt` - subkey count
+    /// * `pattrspck` - pointer to attribute pack
+    /// * `attrspckcnt` - attribute count
+    ///
+    /// # Returns
+    /// * `bool` - true on success
+    fn k2h_set_all(
+        handle: u64,
+        pkey: *const u8,
+        keylength: usize,
+        pval: *const u8,
+        vallength: usize,
+        pskeypck: *const c_void,
+        skeypckcnt: c_int,
+        pattrspck: *const c_void,
+        attrspckcnt: c_int,
+    ) -> bool;
+
+    /// # bool k2h_set_value(k2h_h handle, const unsigned char* pkey, size_t keylength, const unsigned char* pval, size_t vallength)
+    /// k2h_set_value: Set a value in binary
+    ///
+    /// # Arguments
+    /// * `handle` - k2hash handle
+    /// * `pkey` - key pointer
+    /// * `keylength` - key length
+    /// * `pval` - value pointer
+    /// * `vallength` - value length
+    ///
+    /// # Returns
+    /// * `bool` - true on success
+    fn k2h_set_value(
+        handle: u64,
+        pkey: *const u8,
+        keylength: usize,
+        pval: *const u8,
+        vallength: usize,
+    ) -> bool;
+
+    /// # unsigned char* k2h_get_direct_value_wp(k2h_h handle, const unsigned char* pkey, size_t keylength, size_t* pvallength, const char* pass)
+    /// k2h_get_direct_value_wp: Get a value in binary with a password
+    ///
+    /// # Arguments
+    /// * `handle` - k2hash handle
+    /// * `pkey` - key pointer
+    /// * `keylength` - key length
+    /// * `pvallength` - pointer to value length
+    /// * `pass` - password string
+    ///
+    /// # Returns
+    /// * `*mut u8` - pointer to the value which should be freed
+    fn k2h_get_direct_value_wp(
+        handle: u64,
+        pkey: *const u8,
+        keylength: usize,
+        pvallength: *mut usize,
+        pass: *const c_char,
+    ) -> *mut u8;
+
+    /// # bool k2h_set_value_wa(k2h_h handle, const unsigned char* pkey, size_t keylength, const unsigned char* pval, size_t vallength, const char* pass, const time_t* expire)
+    /// k2h_set_value_wa: Set a value in binary with a password and an expiration
+    ///
+    /// # Arguments
+    /// * `handle` - k2hash handle
+    /// * `pkey` - key pointer
+    /// * `keylength` - key length
+    /// * `pval` - value pointer
+    /// * `vallength` - value length
+    /// * `pass` - password string
+    /// * `expire` - pointer to expiration time
+    ///
+    /// # Returns
+    /// * `bool` - true on success
+    fn k2h_set_value_wa(
+        handle: u64,
+        pkey: *const u8,
+        keylength: usize,
+        pval: *const u8,
+        vallength: usize,
+        pass: *const c_char,
+        expire: *const c_ulonglong,
+    ) -> bool;
+
+    /// # bool k2h_set_subkeys(k2h_h handle, const unsigned char* pkey, size_t keylength, const PK2HKEYPCK pskeypck, int skeypckcnt)
+    /// k2h_set_subkeys: Replace subkeys in binary
+    ///
+    /// # Arguments
+    /// * `handle` - k2hash handle
+    /// * `pkey` - key pointer
+    /// * `keylength` - key length
+    /// * `pskeypck` - pointer to subkey pack
+    /// * `skeypckcnt` - subkey count
+    ///
+    /// # Returns
+    /// * `bool` - true on success
+    fn k2h_set_subkeys(
+        handle: u64,
+        pkey: *const u8,
+        keylength: usize,
+        pskeypck: *const c_void,
+        skeypckcnt: c_int,
+    ) -> bool;
+
+    /// # bool k2h_remove(k2h_h handle, const unsigned char* pkey, size_t keylength)
+    /// k2h_remove: Remove a key in binary
+    ///
+    /// # Arguments
+    /// * `handle` - k2hash handle
+    /// * `pkey` - key pointer
+    /// * `keylength` - key length
+    ///
+    /// # Returns
+    /// * `bool` - true on success
+    fn k2h_remove(handle: u64, pkey: *const u8, keylength: usize) -> bool;
+
+    /// # bool k2h_rename(k2h_h handle, const unsigned char* pkey, size_t keylength, const unsigned char* pnewkey, size_t newkeylength)
+    /// k2h_rename: Rename a key in binary
+    ///
+    /// # Arguments
+    /// * `handle` - k2hash handle
+    /// * `pkey` - key pointer
+    /// * `keylength` - key length
+    /// * `pnewkey` - new key pointer
+    /// * `newkeylength` - new key length
+    ///
+    /// # Returns
+    /// * `bool` - true on success
+    fn k2h_rename(
+        handle: u64,
+        pkey: *const u8,
+        keylength: usize,
+        pnewkey: *const u8,
+        newkeylength: usize,
+    ) -> bool;
+
     /// # get transaction API
     /// # int k2h_get_transaction_archive_fd(k2h_h handle)
     /// k2h_get_transaction_archive_fd: Get transaction archive file descriptor
@@ -845,6 +1211,16 @@ extern "C" {
     /// * `bool` - true on success
     fn k2h_print_state(handle: u64, stream: *mut c_void) -> bool;
 
+    /// # PK2HSTATE k2h_get_state(k2h_h handle)
+    /// k2h_get_state: Get state information
+    ///
+    /// # Arguments
+    /// * `handle` - k2hash handle
+    ///
+    /// # Returns
+    /// * `*mut K2hState` - pointer to the state which should be freed
+    fn k2h_get_state(handle: u64) -> *mut K2hState;
+
     /// # void k2h_print_version(FILE* stream)
     /// k2h_print_version: Print version information
     ///
@@ -981,6 +1357,46 @@ extern "C" {
     /// * `bool` - true on success
     fn k2h_set_debug_level_message() -> bool;
 
+    /// # bool k2h_set_debug_file(const char* filepath)
+    /// k2h_set_debug_file: Set the file which the debug output is written to
+    ///
+    /// # Arguments
+    /// * `filepath` - file path
+    ///
+    /// # Returns
+    /// * `bool` - true on success
+    fn k2h_set_debug_file(filepath: *const c_char) -> bool;
+
+    /// # bool k2h_unset_debug_file(void)
+    /// k2h_unset_debug_file: Write the debug output to stderr again
+    ///
+    /// # Arguments
+    /// * `void` - no arguments
+    ///
+    /// # Returns
+    /// * `bool` - true on success
+    fn k2h_unset_debug_file() -> bool;
+
+    /// # bool k2h_set_bumpup_debug_signal_user1(void)
+    /// k2h_set_bumpup_debug_signal_user1: Raise the debug level by SIGUSR1
+    ///
+    /// # Arguments
+    /// * `void` - no arguments
+    ///
+    /// # Returns
+    /// * `bool` - true on success
+    fn k2h_set_bumpup_debug_signal_user1() -> bool;
+
+    /// # bool k2h_load_debug_env(void)
+    /// k2h_load_debug_env: Load the debug level and file from K2HDBGMODE and K2HDBGFILE
+    ///
+    /// # Arguments
+    /// * `void` - no arguments
+    ///
+    /// # Returns
+    /// * `bool` - true on success
+    fn k2h_load_debug_env() -> bool;
+
     /// # set value
     /// # bool k2h_set_str_value_wa(k2h_h handle, const char* pkey, const char* pval, const char* pass, const time_t* expire)
     /// k2h_set_str_value_wa: Set string value with optional password and expiration
@@ -1084,12 +1500,14 @@ extern "C" {
 /// assert_eq!(k2hkey.next(), Some("hello".to_string())); // internally calls k2h_find_next.
 /// assert_eq!(k2hkey.next(), None); // internally calls k2h_find_next, but no more keys are available, so returns None.
 /// ```
+#[cfg(feature = "libk2hash")]
 pub struct K2hashKey {
     k2h_handle: u64,
     key: Option<String>,
     handle: u64,
 }
 
+#[cfg(feature = "libk2hash")]
 impl K2hashKey {
     pub fn new(k2h_handle: u64, key: Option<String>) -> Result<Self, &'static str> {
         if k2h_handle == 0 {
@@ -1124,6 +1542,7 @@ impl K2hashKey {
 //     encpass: *const c_char,
 // ) -> bool;
 
+#[cfg(feature = "libk2hash")]
 impl Iterator for K2hashKey {
     type Item = String;
 
@@ -1147,6 +1566,199 @@ impl Iterator for K2hashKey {
     }
 }
 
+/// QueueKind represents the kind of a queue found by `K2hash::list_queues`.
+#[cfg(feature = "libk2hash")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueueKind {
+    /// A queue of values, used by `Queue`.
+    Queue,
+    /// A queue of keys whose values are stored as keys of their own, used by `KeyQueue`.
+    KeyQueue,
+}
+
+#[cfg(feature = "libk2hash")]
+impl QueueKind {
+    fn name(&self) -> &'static str {
+        match self {
+            QueueKind::Queue => "queue",
+            QueueKind::KeyQueue => "keyqueue",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "queue" => Some(QueueKind::Queue),
+            "keyqueue" => Some(QueueKind::KeyQueue),
+            _ => None,
+        }
+    }
+}
+
+/// QueueInfo represents a queue found by `K2hash::list_queues`.
+#[cfg(feature = "libk2hash")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueueInfo {
+    /// The prefix passed to the constructor, which is empty for the default prefix of libk2hash.
+    pub prefix: Vec<u8>,
+    /// The kind of the queue.
+    pub kind: QueueKind,
+    /// Whether the queue was opened as FIFO, or as LIFO.
+    pub fifo: bool,
+    /// The number of elements in the queue.
+    pub qsize: usize,
+}
+
+/// K2hashStats represents the statistics of a K2hash database returned by `K2hash::stats`.
+#[cfg(feature = "libk2hash")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct K2hashStats {
+    /// The version of the file format.
+    pub version: String,
+    /// The version of the hash function.
+    pub hash_version: String,
+    /// The version of the transaction plugin.
+    pub trans_version: String,
+    /// The number of transaction threads.
+    pub trans_pool_count: i32,
+    /// The max mask of the hash table.
+    pub max_mask: u64,
+    /// The min mask of the hash table.
+    pub min_mask: u64,
+    /// The current mask of the hash table.
+    pub cur_mask: u64,
+    /// The collision mask, which limits the depth of the sub hash tables.
+    ///
+    /// K2HSTATE has no member for the current depth of the collision chains, so it is not
+    /// reported here. `collision_mask_bits` gives the configured depth, and
+    /// `K2hash::dump_table_stats` prints the per table counts.
+    pub collision_mask: u64,
+    /// The max element count of a collision.
+    pub max_element_count: u64,
+    /// The total size of the database.
+    pub total_size: u64,
+    /// The page size.
+    pub page_size: u64,
+    /// The file size, which is 0 for a memory-based database.
+    pub file_size: u64,
+    /// The used size.
+    pub total_used_size: u64,
+    /// The size of the mapped areas.
+    pub total_map_size: u64,
+    /// The size of the element areas.
+    pub total_element_size: u64,
+    /// The size of the page areas.
+    pub total_page_size: u64,
+    /// The number of areas.
+    pub total_area_count: i64,
+    /// The number of elements.
+    pub total_element_count: i64,
+    /// The number of pages.
+    pub total_page_count: i64,
+    /// The number of assigned areas.
+    pub assigned_area_count: i64,
+    /// The number of assigned keys.
+    pub assigned_key_count: i64,
+    /// The number of assigned collision keys.
+    pub assigned_ckey_count: i64,
+    /// The number of assigned elements.
+    pub assigned_element_count: i64,
+    /// The number of assigned pages.
+    pub assigned_page_count: i64,
+    /// The number of free elements.
+    pub unassigned_element_count: i64,
+    /// The number of free pages.
+    pub unassigned_page_count: i64,
+    /// The time of the last update.
+    pub last_update: SystemTime,
+    /// The time of the last area update.
+    pub last_area_update: SystemTime,
+}
+
+#[cfg(feature = "libk2hash")]
+impl K2hashStats {
+    /// Get the free size.
+    pub fn free_size(&self) -> u64 {
+        self.total_size.saturating_sub(self.total_used_size)
+    }
+
+    /// Get the ratio of the used size to the total size.
+    pub fn fill_ratio(&self) -> f64 {
+        if self.total_size == 0 {
+            0.0
+        } else {
+            self.total_used_size as f64 / self.total_size as f64
+        }
+    }
+
+    /// Get the number of bits of the collision mask, which is the configured depth of the sub
+    /// hash tables.
+    pub fn collision_mask_bits(&self) -> u32 {
+        self.collision_mask.count_ones()
+    }
+
+    /// Get the ratio of the assigned pages to all pages.
+    pub fn page_usage(&self) -> f64 {
+        if self.total_page_count <= 0 {
+            0.0
+        } else {
+            self.assigned_page_count as f64 / self.total_page_count as f64
+        }
+    }
+}
+
+#[cfg(feature = "libk2hash")]
+// the casts widen c_long and c_ulong, which are narrower than 64 bits on ILP32 platforms.
+#[allow(clippy::unnecessary_cast)]
+impl From<&K2hState> for K2hashStats {
+    fn from(state: &K2hState) -> Self {
+        let text = |chars: &[c_char]| {
+            let bytes: Vec<u8> = chars
+                .iter()
+                .map(|c| *c as u8)
+                .take_while(|b| *b != 0)
+                .collect();
+            String::from_utf8_lossy(&bytes).into_owned()
+        };
+        let time = |ts: &libc::timespec| {
+            UNIX_EPOCH
+                + Duration::new(
+                    u64::try_from(ts.tv_sec).unwrap_or(0),
+                    u32::try_from(ts.tv_nsec).unwrap_or(0),
+                )
+        };
+        K2hashStats {
+            version: text(&state.version),
+            hash_version: text(&state.hash_version),
+            trans_version: text(&state.trans_version),
+            trans_pool_count: state.trans_pool_count,
+            max_mask: state.max_mask,
+            min_mask: state.min_mask,
+            cur_mask: state.cur_mask,
+            collision_mask: state.collision_mask,
+            max_element_count: state.max_element_count as u64,
+            total_size: state.total_size as u64,
+            page_size: state.page_size as u64,
+            file_size: state.file_size as u64,
+            total_used_size: state.total_used_size as u64,
+            total_map_size: state.total_map_size as u64,
+            total_element_size: state.total_element_size as u64,
+            total_page_size: state.total_page_size as u64,
+            total_area_count: state.total_area_count as i64,
+            total_element_count: state.total_element_count as i64,
+            total_page_count: state.total_page_count as i64,
+            assigned_area_count: state.assigned_area_count as i64,
+            assigned_key_count: state.assigned_key_count as i64,
+            assigned_ckey_count: state.assigned_ckey_count as i64,
+            assigned_element_count: state.assigned_element_count as i64,
+            assigned_page_count: state.assigned_page_count as i64,
+            unassigned_element_count: state.unassigned_element_count as i64,
+            unassigned_page_count: state.unassigned_page_count as i64,
+            last_update: time(&state.last_update),
+            last_area_update: time(&state.last_area_update),
+        }
+    }
+}
+
 /// K2hash struct provides a high-level interface for interacting with the K2hash database.
 ///
 /// # Examples
@@ -1166,10 +1778,16 @@ impl Iterator for K2hashKey {
 ///     "Get operation returned unexpected value"
 /// );
 /// ```
+#[cfg(feature = "libk2hash")]
 pub struct K2hash {
     handle: u64,
+    path: Option<String>,
+    listener: RwLock<Option<Arc<dyn TxListener>>>,
+    settings: Mutex<HandleSettings>,
+    attr_lock: Mutex<()>,
 }
 
+#[cfg(feature = "libk2hash")]
 impl K2hash {
     /// Get the handle of the K2hash database.
     pub fn handle(&self) -> u64 {
@@ -1191,24 +1809,85 @@ impl K2hash {
         }
     }
 
+    /// Write the debug output of the K2hash c-library to a file instead of stderr.
+    pub fn set_debug_file(path: &str) -> Result<(), &'static str> {
+        let path = CString::new(path).unwrap();
+        if path.is_empty() {
+            return Err("path should be passed");
+        }
+        let result = unsafe { k2h_set_debug_file(path.as_ptr()) };
+        if result {
+            Ok(())
+        } else {
+            Err("k2h_set_debug_file returns error")
+        }
+    }
+
+    /// Write the debug output of the K2hash c-library to stderr again.
+    pub fn clear_debug_file() -> Result<(), &'static str> {
+        let result = unsafe { k2h_unset_debug_file() };
+        if result {
+            Ok(())
+        } else {
+            Err("k2h_unset_debug_file returns error")
+        }
+    }
+
+    /// Raise the debug level of the K2hash c-library each time the process receives SIGUSR1.
+    ///
+    /// The level goes round from silent through error, warning and message.
+    pub fn enable_debug_bumpup_on_sigusr1() -> Result<(), &'static str> {
+        let result = unsafe { k2h_set_bumpup_debug_signal_user1() };
+        if result {
+            Ok(())
+        } else {
+            Err("k2h_set_bumpup_debug_signal_user1 returns error")
+        }
+    }
+
+    /// Load the debug level and file of the K2hash c-library from the `K2HDBGMODE` and
+    /// `K2HDBGFILE` environment variables.
+    pub fn load_debug_from_env() -> Result<(), &'static str> {
+        let result = unsafe { k2h_load_debug_env() };
+        if result {
+            Ok(())
+        } else {
+            Err("k2h_load_debug_env returns error")
+        }
+    }
+
     /// Open a key-value database in a file based.
     pub fn open(file: &str) -> Result<Self, &'static str> {
+        check_library_version()?;
         let f = CString::new(file).unwrap();
         let handle = unsafe { k2h_open(f.as_ptr(), false, false, false, 8, 4, 1024, 512) };
         if handle == 0 {
             Err("k2h_open_mem failed")
         } else {
-            Ok(K2hash { handle })
+            Ok(K2hash {
+                handle,
+                path: Some(file.to_string()),
+                listener: RwLock::new(None),
+                settings: Mutex::new(HandleSettings::default()),
+                attr_lock: Mutex::new(()),
+            })
         }
     }
 
     /// Open a memory-based database.
     pub fn open_mem() -> Result<Self, &'static str> {
+        check_library_version()?;
         let handle = unsafe { k2h_open_mem(8, 4, 1024, 512) };
         if handle == 0 {
             Err("k2h_open_mem failed")
         } else {
-            Ok(K2hash { handle })
+            Ok(K2hash {
+                handle,
+                path: None,
+                listener: RwLock::new(None),
+                settings: Mutex::new(HandleSettings::default()),
+                attr_lock: Mutex::new(()),
+            })
         }
     }
 
@@ -1225,53 +1904,59 @@ impl K2hash {
         password: Option<&str>,
         expire_duration: Option<u64>,
     ) -> Result<(), &'static str> {
-        let k = CString::new(key).unwrap();
-        let v = CString::new(value).unwrap();
-        if k.is_empty() || v.is_empty() {
-            return Err("Key and value cannot be empty");
-        }
-        // Option<String> --as_ref()--> Option<&String> --CString::new()-> Option<CString>
-        let pass = password.map(|p| CString::new(p).unwrap());
-        let c_pass = pass.as_ref().map_or(ptr::null(), |p| p.as_ptr());
-        let expire = expire_duration.map(|e| e as c_ulonglong);
-        let c_expire = expire
-            .as_ref()
-            .map_or(ptr::null(), |e| e as *const c_ulonglong);
-        let result =
-            unsafe { k2h_set_str_value_wa(self.handle, k.as_ptr(), v.as_ptr(), c_pass, c_expire) };
-        if result {
-            Ok(())
-        } else {
-            Err("Failed to set value")
-        }
-    }
-
-    /// Get a value from a key.
-    pub fn get(&self, key: &str) -> Result<Option<String>, &'static str> {
-        return self.get_with_options(key, None);
-    }
-
+        instrument::observe("set", Some(key.len()), || {
+            let k = CString::new(key).unwrap();
+            let v = CString::new(value).unwrap();
+            if k.is_empty() || v.is_empty() {
+                return Err("Key and value cannot be empty");
+            }
+            // Option<String> --as_ref()--> Option<&String> --CString::new()-> Option<CString>
+            let pass = password.map(|p| CString::new(p).unwrap());
+            let c_pass = pass.as_ref().map_or(ptr::null(), |p| p.as_ptr());
+            let expire = expire_duration.map(|e| e as c_ulonglong);
+            let c_expire = expire
+                .as_ref()
+                .map_or(ptr::null(), |e| e as *const c_ulonglong);
+            let result = unsafe {
+                k2h_set_str_value_wa(self.handle, k.as_ptr(), v.as_ptr(), c_pass, c_expire)
+            };
+            if result {
+                self.notify(|l| l.on_set(key, value));
+                Ok(())
+            } else {
+                Err("Failed to set value")
+            }
+        })
+    }
+
+    /// Get a value from a key.
+    pub fn get(&self, key: &str) -> Result<Option<String>, &'static str> {
+        return self.get_with_options(key, None);
+    }
+
     /// Get a value from a key with options.
     pub fn get_with_options(
         &self,
         key: &str,
         password: Option<&str>,
     ) -> Result<Option<String>, &'static str> {
-        let k = CString::new(key).unwrap();
-        if k.is_empty() {
-            return Err("Key cannot be empty");
-        }
-        // Option<&str> --CString::new()-> Option<CString>
-        let pass = password.map(|p| CString::new(p).unwrap());
-        let c_pass = pass.as_ref().map_or(ptr::null(), |p| p.as_ptr());
-        let ptr = unsafe { k2h_get_str_direct_value_wp(self.handle, k.as_ptr(), c_pass) };
+        instrument::observe("get", Some(key.len()), || {
+            let k = CString::new(key).unwrap();
+            if k.is_empty() {
+                return Err("Key cannot be empty");
+            }
+            // Option<&str> --CString::new()-> Option<CString>
+            let pass = password.map(|p| CString::new(p).unwrap());
+            let c_pass = pass.as_ref().map_or(ptr::null(), |p| p.as_ptr());
+            let ptr = unsafe { k2h_get_str_direct_value_wp(self.handle, k.as_ptr(), c_pass) };
 
-        if ptr.is_null() {
-            Err("Failed to get result")
-        } else {
-            let cstr = unsafe { CStr::from_ptr(ptr) };
-            Ok(Some(cstr.to_string_lossy().into_owned()))
-        }
+            if ptr.is_null() {
+                Err("Failed to get result")
+            } else {
+                let cstr = unsafe { CStr::from_ptr(ptr) };
+                Ok(Some(cstr.to_string_lossy().into_owned()))
+            }
+        })
     }
 
     /// Add an attribute plugin library.
@@ -1282,6 +1967,8 @@ impl K2hash {
         }
         let result = unsafe { k2h_add_attr_plugin_library(self.handle, path.as_ptr()) };
         if result {
+            let path = path.to_string_lossy().into_owned();
+            self.settings.lock().unwrap().plugins.push(path);
             Ok(())
         } else {
             Err("add_attribute_plugin_lib returns error")
@@ -1296,6 +1983,12 @@ impl K2hash {
         }
         let result = unsafe { k2h_add_attr_crypt_pass(self.handle, password.as_ptr(), false) };
         if result {
+            let password = password.to_string_lossy().into_owned();
+            self.settings
+                .lock()
+                .unwrap()
+                .passwords
+                .push((password, false));
             Ok(())
         } else {
             Err("add_decryption_password returns error")
@@ -1316,34 +2009,83 @@ impl K2hash {
         password: Option<String>,
         expire_duration: Option<u64>,
     ) -> Result<(), &'static str> {
-        let k = CString::new(key).unwrap();
-        let sk = CString::new(subkey).unwrap();
-        let sv = CString::new(subval).unwrap();
-        // Option<String> --as_ref()--> Option<&String> --CString::new()-> Option<CString>
-        let pass = password.as_ref().map(|p| CString::new(p.as_str()).unwrap());
-        let c_pass = pass.as_ref().map_or(ptr::null(), |p| p.as_ptr());
-        let expire = expire_duration.map(|e| e as c_ulonglong);
-        let c_expire = expire
-            .as_ref()
-            .map_or(ptr::null(), |e| e as *const c_ulonglong);
-        let result = unsafe {
-            k2h_add_subkey_wa(
-                self.handle,
-                k.as_bytes_with_nul().as_ptr(),
-                k.as_bytes_with_nul().len(),
-                sk.as_bytes_with_nul().as_ptr(),
-                sk.as_bytes_with_nul().len(),
-                sv.as_bytes_with_nul().as_ptr(),
-                sv.as_bytes_with_nul().len(),
-                c_pass,
-                c_expire,
-            )
-        };
-        if result {
-            Ok(())
-        } else {
-            Err("k2h_add_subkey_wa returns error")
-        }
+        instrument::observe("add_subkey", Some(key.len()), || {
+            let k = CString::new(key).unwrap();
+            let sk = CString::new(subkey).unwrap();
+            let sv = CString::new(subval).unwrap();
+            // Option<String> --as_ref()--> Option<&String> --CString::new()-> Option<CString>
+            let pass = password.as_ref().map(|p| CString::new(p.as_str()).unwrap());
+            let c_pass = pass.as_ref().map_or(ptr::null(), |p| p.as_ptr());
+            let expire = expire_duration.map(|e| e as c_ulonglong);
+            let c_expire = expire
+                .as_ref()
+                .map_or(ptr::null(), |e| e as *const c_ulonglong);
+            let result = unsafe {
+                k2h_add_subkey_wa(
+                    self.handle,
+                    k.as_bytes_with_nul().as_ptr(),
+                    k.as_bytes_with_nul().len(),
+                    sk.as_bytes_with_nul().as_ptr(),
+                    sk.as_bytes_with_nul().len(),
+                    sv.as_bytes_with_nul().as_ptr(),
+                    sv.as_bytes_with_nul().len(),
+                    c_pass,
+                    c_expire,
+                )
+            };
+            if result {
+                self.notify(|l| l.on_add_subkey(key, subkey, subval));
+                Ok(())
+            } else {
+                Err("k2h_add_subkey_wa returns error")
+            }
+        })
+    }
+
+    /// Replay a transaction file into the K2hash database.
+    ///
+    /// Records are applied in order from `start_offset`, or from the offset kept in the
+    /// checkpoint file. Pass `end_offset` of the report as `start_offset` of the next call, or
+    /// set `ApplyTxOptions::checkpoint`, so that the records are not replayed. Replay is
+    /// at-least-once, as the offset is stored after the record it covers. A dry run simulates
+    /// the records on top of the current database without changing it.
+    pub fn apply_tx_log(
+        &self,
+        path: &str,
+        options: ApplyTxOptions,
+    ) -> Result<ApplyTxReport, &'static str> {
+        instrument::observe("apply_tx_log", None, || {
+            let checkpoint = match options.checkpoint {
+                Some(ref path) => read_checkpoint(path)?,
+                None => None,
+            };
+            let start_offset = checkpoint.unwrap_or(0).max(options.start_offset);
+            let mut reader = TxLogReader::open(path)?;
+            reader.seek_to(start_offset)?;
+            let mut report = ApplyTxReport {
+                end_offset: start_offset,
+                ..Default::default()
+            };
+            let mut state = TxState::new(self.handle, options.dry_run);
+            for record in reader.by_ref() {
+                let record = record?;
+                if state.apply(&record)? {
+                    report.changes.push(TxChange {
+                        offset: record.offset,
+                        operation: record.operation,
+                        key: record.key_str(),
+                    });
+                } else {
+                    report.unchanged += 1;
+                }
+                report.end_offset = record.end_offset;
+                if let (Some(path), false) = (options.checkpoint.as_deref(), options.dry_run) {
+                    write_checkpoint(path, report.end_offset)?;
+                }
+            }
+            report.truncated = reader.is_truncated();
+            Ok(report)
+        })
     }
 
     /// Start a transaction.
@@ -1359,47 +2101,49 @@ impl K2hash {
         param: Option<String>,
         expire_duration: Option<u64>,
     ) -> Result<(), &'static str> {
-        let txf = CString::new(txfile).unwrap();
+        instrument::observe("begin_tx", None, || {
+            let txf = CString::new(txfile).unwrap();
 
-        // Option<String> --as_ref()--> Option<&String> --CString::new()-> Option<CString>
-        let pre = prefix.as_ref().map(|p| CString::new(p.as_str()).unwrap());
-        // Option<CString> -> const unsigned char*
-        let c_pre = pre
-            .as_ref()
-            .map_or(ptr::null(), |p| p.as_bytes_with_nul().as_ptr());
-        // Option<usize>
-        let pre_length = pre.as_ref().map(|c| c.as_bytes_with_nul().len());
-
-        // Option<String> --as_ref()--> Option<&String> --CString::new()-> Option<CString>
-        let par = param.as_ref().map(|p| CString::new(p.as_str()).unwrap());
-        // Option<CString> -> const unsigned char*
-        let c_par = par
-            .as_ref()
-            .map_or(ptr::null(), |p| p.as_bytes_with_nul().as_ptr());
-        // Option<usize>
-        let par_length = par.as_ref().map(|c| c.as_bytes_with_nul().len());
+            // Option<String> --as_ref()--> Option<&String> --CString::new()-> Option<CString>
+            let pre = prefix.as_ref().map(|p| CString::new(p.as_str()).unwrap());
+            // Option<CString> -> const unsigned char*
+            let c_pre = pre
+                .as_ref()
+                .map_or(ptr::null(), |p| p.as_bytes_with_nul().as_ptr());
+            // Option<usize>
+            let pre_length = pre.as_ref().map(|c| c.as_bytes_with_nul().len());
+
+            // Option<String> --as_ref()--> Option<&String> --CString::new()-> Option<CString>
+            let par = param.as_ref().map(|p| CString::new(p.as_str()).unwrap());
+            // Option<CString> -> const unsigned char*
+            let c_par = par
+                .as_ref()
+                .map_or(ptr::null(), |p| p.as_bytes_with_nul().as_ptr());
+            // Option<usize>
+            let par_length = par.as_ref().map(|c| c.as_bytes_with_nul().len());
 
-        let expire = expire_duration.map(|e| e as c_ulonglong);
-        let c_expire = expire
-            .as_ref()
-            .map_or(ptr::null(), |e| e as *const c_ulonglong);
-        let result = unsafe {
-            k2h_transaction_param_we(
-                self.handle,
-                true,
-                txf.as_ptr(),
-                c_pre,
-                pre_length.unwrap_or(0), // Option<usize> -> usize
-                c_par,
-                par_length.unwrap_or(0), // Option<usize> -> usize
-                c_expire,
-            )
-        };
-        if result {
-            Ok(())
-        } else {
-            Err("begin_tx returns error")
-        }
+            let expire = expire_duration.map(|e| e as c_ulonglong);
+            let c_expire = expire
+                .as_ref()
+                .map_or(ptr::null(), |e| e as *const c_ulonglong);
+            let result = unsafe {
+                k2h_transaction_param_we(
+                    self.handle,
+                    true,
+                    txf.as_ptr(),
+                    c_pre,
+                    pre_length.unwrap_or(0), // Option<usize> -> usize
+                    c_par,
+                    par_length.unwrap_or(0), // Option<usize> -> usize
+                    c_expire,
+                )
+            };
+            if result {
+                Ok(())
+            } else {
+                Err("begin_tx returns error")
+            }
+        })
     }
 
     /// Create a new K2hash database in a file.
@@ -1451,18 +2195,130 @@ impl K2hash {
         path: &str,
         is_skip_error: bool,
     ) -> Result<(), &'static str> {
-        let p = CString::new(path).unwrap();
-        if p.is_empty() {
-            return Err("path cannot be empty");
-        }
-        let i: bool = is_skip_error;
+        instrument::observe("dump_to_file", None, || {
+            let p = CString::new(path).unwrap();
+            if p.is_empty() {
+                return Err("path cannot be empty");
+            }
+            let i: bool = is_skip_error;
 
-        let result = unsafe { k2h_put_archive(self.handle, p.as_ptr(), i) };
-        if result {
-            Ok(())
-        } else {
-            Err("k2h_create returns error")
-        }
+            let result = unsafe { k2h_put_archive(self.handle, p.as_ptr(), i) };
+            if result {
+                Ok(())
+            } else {
+                Err("k2h_create returns error")
+            }
+        })
+    }
+
+    /// Write the table statistics of the K2hash database to the writer.
+    pub fn dump_table_stats_to<W: Write>(
+        &self,
+        writer: &mut W,
+        dump_level: DumpLevel,
+    ) -> Result<(), &'static str> {
+        instrument::observe("dump_table_stats", None, || {
+            let result = write_stream_to(writer, |stream| unsafe {
+                match dump_level {
+                    DumpLevel::HEADER => k2h_dump_head(self.handle, stream),
+                    DumpLevel::HASHTABLE => k2h_dump_keytable(self.handle, stream),
+                    DumpLevel::SUBHASHTABLE => k2h_dump_full_keytable(self.handle, stream),
+                    DumpLevel::ELEMENT => k2h_dump_elementtable(self.handle, stream),
+                    DumpLevel::PAGE => k2h_dump_full(self.handle, stream),
+                }
+            })?;
+            if result {
+                Ok(())
+            } else {
+                Err("k2h_dump_head returns error")
+            }
+        })
+    }
+
+    /// Get the table statistics of the K2hash database as a string.
+    pub fn dump_table_stats_string(&self, dump_level: DumpLevel) -> Result<String, &'static str> {
+        let mut output = Vec::new();
+        self.dump_table_stats_to(&mut output, dump_level)?;
+        Ok(String::from_utf8_lossy(&output).into_owned())
+    }
+
+    /// Write the data statistics of the K2hash database to the writer.
+    pub fn dump_data_stats_to<W: Write>(&self, writer: &mut W) -> Result<(), &'static str> {
+        instrument::observe("dump_data_stats", None, || {
+            let result = write_stream_to(writer, |stream| unsafe {
+                k2h_print_state(self.handle, stream)
+            })?;
+            if result {
+                Ok(())
+            } else {
+                Err("k2h_print_state returns error")
+            }
+        })
+    }
+
+    /// Get the data statistics of the K2hash database as a string.
+    pub fn dump_data_stats_string(&self) -> Result<String, &'static str> {
+        let mut output = Vec::new();
+        self.dump_data_stats_to(&mut output)?;
+        Ok(String::from_utf8_lossy(&output).into_owned())
+    }
+
+    /// Write the attribute information of the K2hash database to the writer.
+    pub fn dump_attributes_to<W: Write>(&self, writer: &mut W) -> Result<(), &'static str> {
+        instrument::observe("dump_attributes", None, || {
+            let result = write_stream_to(writer, |stream| unsafe {
+                k2h_print_attr_information(self.handle, stream)
+            })?;
+            if result {
+                Ok(())
+            } else {
+                Err("k2h_print_attr_information returns error")
+            }
+        })
+    }
+
+    /// Get the attribute information of the K2hash database as a string.
+    pub fn dump_attributes_string(&self) -> Result<String, &'static str> {
+        let mut output = Vec::new();
+        self.dump_attributes_to(&mut output)?;
+        Ok(String::from_utf8_lossy(&output).into_owned())
+    }
+
+    /// Write the attribute plugins of the K2hash database to the writer.
+    pub fn dump_attribute_plugins_to<W: Write>(&self, writer: &mut W) -> Result<(), &'static str> {
+        instrument::observe("dump_attribute_plugins", None, || {
+            let result = write_stream_to(writer, |stream| unsafe {
+                k2h_print_attr_version(self.handle, stream)
+            })?;
+            if result {
+                Ok(())
+            } else {
+                Err("k2h_print_attr_version returns error")
+            }
+        })
+    }
+
+    /// Get the attribute plugins of the K2hash database as a string.
+    pub fn dump_attribute_plugins_string(&self) -> Result<String, &'static str> {
+        let mut output = Vec::new();
+        self.dump_attribute_plugins_to(&mut output)?;
+        Ok(String::from_utf8_lossy(&output).into_owned())
+    }
+
+    /// Write the k2hash C-library version to the writer.
+    pub fn version_to<W: Write>(writer: &mut W) -> Result<(), &'static str> {
+        write_stream_to(writer, |stream| {
+            unsafe { k2h_print_version(stream) };
+            true
+        })?;
+        Ok(())
+    }
+
+    /// Get the k2hash C-library version as a string.
+    pub fn version_string() -> Result<String, &'static str> {
+        let mut output = Vec::new();
+        K2hash::version_to(&mut output)?;
+        Ok(String::from_utf8_lossy(&output).into_owned())
     }
 
     /// Enable encryption.
@@ -1479,6 +2335,7 @@ impl K2hash {
             )
         };
         if result {
+            self.settings.lock().unwrap().encryption = Some(enable);
             Ok(())
         } else {
             Err("k2h_create returns error")
@@ -1499,6 +2356,7 @@ impl K2hash {
             )
         };
         if result {
+            self.settings.lock().unwrap().history = Some(enable);
             Ok(())
         } else {
             Err("k2h_create returns error")
@@ -1519,6 +2377,7 @@ impl K2hash {
             )
         };
         if result {
+            self.settings.lock().unwrap().mtime = Some(enable);
             Ok(())
         } else {
             Err("k2h_create returns error")
@@ -1530,50 +2389,48 @@ impl K2hash {
         &self,
         key: &str,
     ) -> Result<Option<HashMap<String, String>>, &'static str> {
-        let k = CString::new(key).unwrap();
-        if k.is_empty() {
-            return Err("key should be passed");
+        let attrs = self.get_attributes_raw(key)?;
+        if attrs.is_empty() {
+            return Err("k2h_get_direct_attrs returns error");
         }
-        let k_length = k.as_bytes_with_nul().len();
-        let mut pattrspckcnt: c_int = 0; // mutable c_int
-                                         // let mut pskeypckcnt: c_int = 0;
-        let result = unsafe {
-            k2h_get_direct_attrs(
-                self.handle,
-                k.as_bytes_with_nul().as_ptr(), // byte
-                k_length,
-                &mut pattrspckcnt, // mutable pointer to c_int
-            )
+        let to_string = |bytes: &[u8]| {
+            let bytes = bytes.strip_suffix(&[0]).unwrap_or(bytes);
+            String::from_utf8_lossy(bytes).into_owned()
         };
-        println!("pattrspckcnt: {}", pattrspckcnt);
-        if !result.is_null() && pattrspckcnt > 0 {
-            let mut attrs: HashMap<String, String> = HashMap::new();
-            for i in 0..pattrspckcnt {
-                // Assuming result is a pointer to an array of AttrPack structures
-                let attr_pack: &K2hAttrPack =
-                    unsafe { &*(result as *const K2hAttrPack).add(i as usize) };
-                let key = unsafe {
-                    CStr::from_ptr(attr_pack.pkey as *const c_char)
-                        .to_string_lossy()
-                        .into_owned()
-                };
-                let val = unsafe {
-                    CStr::from_ptr(attr_pack.pval as *const c_char)
-                        .to_string_lossy()
-                        .into_owned()
-                };
-                // println!("key: {}", key);
-                let keylength = unsafe { attr_pack.keylength as usize };
-                let vallength = unsafe { attr_pack.vallength as usize };
-                println!("keylength: {}", keylength);
-                println!("vallength: {}", vallength);
-                attrs.insert(key, val);
-            }
-            // Return the attributes as a Result
-            Ok(Some(attrs))
-        } else {
-            Err("k2h_get_direct_attrs returns error")
-        }
+        Ok(Some(
+            attrs
+                .iter()
+                .map(|(name, val)| (to_string(name), to_string(val)))
+                .collect(),
+        ))
+    }
+
+    /// Get attributes of a key in binary.
+    ///
+    /// Names and values are returned as stored, including the trailing NUL of string attributes.
+    /// Returns an error if the key does not exist.
+    pub fn get_attributes_raw(&self, key: &str) -> Result<HashMap<Vec<u8>, Vec<u8>>, &'static str> {
+        instrument::observe("get_attributes", Some(key.len()), || {
+            if key.is_empty() {
+                return Err("key should be passed");
+            }
+            let attrs = read_attributes(self.handle, key);
+            // a key with attributes exists, so only a key without them is looked up.
+            if attrs.is_empty() {
+                let k = CString::new(key).unwrap();
+                if read_value_raw(self.handle, k.as_bytes_with_nul()).is_none() {
+                    return Err("key does not exist");
+                }
+            }
+            Ok(attrs.into_iter().collect())
+        })
+    }
+
+    /// Get the builtin attributes of a key in typed fields.
+    ///
+    /// Returns an error if the key does not exist.
+    pub fn get_metadata(&self, key: &str) -> Result<KeyMetadata, &'static str> {
+        Ok(KeyMetadata::from(self.get_attributes_raw(key)?))
     }
 
     // # get subkeys API
@@ -1584,40 +2441,42 @@ impl K2hash {
 
     /// Get subkeys of a key.
     pub fn get_subkeys(&self, key: &str) -> Result<Option<Vec<String>>, &'static str> {
-        let k = CString::new(key).unwrap();
-        if k.is_empty() {
-            return Err("key should be passed");
-        }
-        let k_length = k.as_bytes_with_nul().len();
-        let mut pskeypckcnt: c_int = 0;
-        let result = unsafe {
-            k2h_get_direct_subkeys(
-                self.handle,
-                k.as_bytes_with_nul().as_ptr(),
-                k_length,
-                &mut pskeypckcnt,
-            )
-        };
-        if !result.is_null() && pskeypckcnt > 0 {
-            // println!("pskeypckcnt: {}", pskeypckcnt);
-            let mut keys: Vec<String> = Vec::new();
-            for i in 0..pskeypckcnt {
-                // Assuming result is a pointer to an array of KeyPack structures
-                let key_pack: &K2hKeyPack =
-                    unsafe { &*(result as *const K2hKeyPack).add(i as usize) };
-                let key = unsafe {
-                    CStr::from_ptr(key_pack.pkey as *const c_char)
-                        .to_string_lossy()
-                        .into_owned()
-                };
-                // println!("key: {}", key);
-                keys.push(key);
+        instrument::observe("get_subkeys", Some(key.len()), || {
+            let k = CString::new(key).unwrap();
+            if k.is_empty() {
+                return Err("key should be passed");
             }
-            // Return the keys as a Result
-            Ok(Some(keys))
-        } else {
-            Err("k2h_get_direct_subkeys returns error")
-        }
+            let k_length = k.as_bytes_with_nul().len();
+            let mut pskeypckcnt: c_int = 0;
+            let result = unsafe {
+                k2h_get_direct_subkeys(
+                    self.handle,
+                    k.as_bytes_with_nul().as_ptr(),
+                    k_length,
+                    &mut pskeypckcnt,
+                )
+            };
+            if !result.is_null() && pskeypckcnt > 0 {
+                // println!("pskeypckcnt: {}", pskeypckcnt);
+                let mut keys: Vec<String> = Vec::new();
+                for i in 0..pskeypckcnt {
+                    // Assuming result is a pointer to an array of KeyPack structures
+                    let key_pack: &K2hKeyPack =
+                        unsafe { &*(result as *const K2hKeyPack).add(i as usize) };
+                    let key = unsafe {
+                        CStr::from_ptr(key_pack.pkey as *const c_char)
+                            .to_string_lossy()
+                            .into_owned()
+                    };
+                    // println!("key: {}", key);
+                    keys.push(key);
+                }
+                // Return the keys as a Result
+                Ok(Some(keys))
+            } else {
+                Err("k2h_get_direct_subkeys returns error")
+            }
+        })
     }
 
     /// Get transaction file descriptor.
@@ -1641,23 +2500,93 @@ impl K2hash {
         }
     }
 
+    /// List the queues opened through this crate, their kinds, their modes and their sizes.
+    ///
+    /// Marker keys of libk2hash carry neither the kind nor the FIFO/LIFO mode of a queue, so
+    /// `Queue::new` and `KeyQueue::new` register them as subkeys of the `QUEUE_REGISTRY` key.
+    /// Queues written by other clients, or by versions of this crate without the registry, are
+    /// not listed. A prefix opened in both modes, or as both kinds, is listed once for each.
+    ///
+    /// This costs a handle and a count per registered queue, not a scan of the database.
+    pub fn list_queues(&self) -> Result<Vec<QueueInfo>, &'static str> {
+        let registry = CString::new(QUEUE_REGISTRY).unwrap();
+        let entry_prefix = format!("{}.", QUEUE_REGISTRY);
+        let mut queues = Vec::new();
+        for subkey in read_subkeys_raw(self.handle, registry.as_bytes_with_nul()) {
+            let subkey = subkey.strip_suffix(&[0]).unwrap_or(&subkey);
+            let Some(entry) = subkey.strip_prefix(entry_prefix.as_bytes()) else {
+                continue;
+            };
+            let mut fields = entry.splitn(3, |b| *b == b'.');
+            let (Some(kind), Some(mode), Some(prefix)) =
+                (fields.next(), fields.next(), fields.next())
+            else {
+                continue;
+            };
+            let Some(kind) = std::str::from_utf8(kind)
+                .ok()
+                .and_then(QueueKind::from_name)
+            else {
+                continue;
+            };
+            let fifo = mode == b"fifo";
+            let c_prefix = match prefix {
+                [] => None,
+                prefix => match CString::new(prefix) {
+                    Ok(c_prefix) => Some(c_prefix),
+                    Err(_) => continue,
+                },
+            };
+            let pref = c_prefix.as_ref().map_or(ptr::null(), |c| c.as_ptr());
+            let count = match kind {
+                QueueKind::Queue => {
+                    let qhandle = unsafe { k2h_q_handle_str_prefix(self.handle, fifo, pref) };
+                    if qhandle == 0 {
+                        continue;
+                    }
+                    let count = unsafe { k2h_q_count(qhandle) };
+                    unsafe { k2h_q_free(qhandle) };
+                    count
+                }
+                QueueKind::KeyQueue => {
+                    let qhandle = unsafe { k2h_keyq_handle_str_prefix(self.handle, fifo, pref) };
+                    if qhandle == 0 {
+                        continue;
+                    }
+                    let count = unsafe { k2h_keyq_count(qhandle) };
+                    unsafe { k2h_keyq_free(qhandle) };
+                    count
+                }
+            };
+            queues.push(QueueInfo {
+                prefix: prefix.to_vec(),
+                kind,
+                fifo,
+                qsize: count.max(0) as usize,
+            });
+        }
+        Ok(queues)
+    }
+
     /// Load a K2hash database from a file.
     pub fn load_from_file(
         &self,
         path: &str,
         is_skip_error: Option<bool>,
     ) -> Result<(), &'static str> {
-        let p = CString::new(path).unwrap();
-        let skip_error = is_skip_error.unwrap_or(true);
-        if p.is_empty() {
-            return Err("Failed to get path");
-        }
-        let result = unsafe { k2h_load_archive(self.handle, p.as_ptr(), skip_error) };
-        if result {
-            Ok(())
-        } else {
-            Err("k2h_load_archive returns error")
-        }
+        instrument::observe("load_from_file", None, || {
+            let p = CString::new(path).unwrap();
+            let skip_error = is_skip_error.unwrap_or(true);
+            if p.is_empty() {
+                return Err("Failed to get path");
+            }
+            let result = unsafe { k2h_load_archive(self.handle, p.as_ptr(), skip_error) };
+            if result {
+                Ok(())
+            } else {
+                Err("k2h_load_archive returns error")
+            }
+        })
     }
 
     /// Print attribute plugins of the K2hash database.
@@ -1744,61 +2673,71 @@ impl K2hash {
         key: &str,
         remove_all_subkeys: bool,
     ) -> Result<(), &'static str> {
-        let k = CString::new(key).unwrap();
-        let b_remove_all_subkeys = remove_all_subkeys;
-        if k.is_empty() {
-            return Err("key should be passed");
-        }
-        if b_remove_all_subkeys {
-            let result = unsafe { k2h_remove_str_all(self.handle, k.as_ptr()) };
-            if result {
-                Ok(())
-            } else {
-                Err("Failed to h.k2h_remove_str_all")
+        instrument::observe("remove", Some(key.len()), || {
+            let k = CString::new(key).unwrap();
+            let b_remove_all_subkeys = remove_all_subkeys;
+            if k.is_empty() {
+                return Err("key should be passed");
             }
-        } else {
-            let result = unsafe { k2h_remove_str(self.handle, k.as_ptr()) };
-            if result {
-                Ok(())
+            if b_remove_all_subkeys {
+                let result = unsafe { k2h_remove_str_all(self.handle, k.as_ptr()) };
+                if result {
+                    self.notify(|l| l.on_remove(key));
+                    Ok(())
+                } else {
+                    Err("Failed to h.k2h_remove_str_all")
+                }
             } else {
-                Err("Failed to set value")
+                let result = unsafe { k2h_remove_str(self.handle, k.as_ptr()) };
+                if result {
+                    self.notify(|l| l.on_remove(key));
+                    Ok(())
+                } else {
+                    Err("Failed to set value")
+                }
             }
-        }
+        })
     }
 
     /// Remove a subkey of a key from the K2hash database.
     pub fn remove_subkeys(&self, key: &str, subkeys: Vec<&str>) -> Result<(), &'static str> {
-        let k = CString::new(key).unwrap();
-        if k.is_empty() {
-            return Err("key should be passed");
-        }
-        let v_subkeys_iter = subkeys.iter();
-        for skey in v_subkeys_iter {
-            let sk = CString::new(*skey).unwrap();
-            let result = unsafe { k2h_remove_str_subkey(self.handle, k.as_ptr(), sk.as_ptr()) };
-            if result == false {
-                return Err("Failed to h.k2h_remove_str_all");
+        instrument::observe("remove_subkeys", Some(key.len()), || {
+            let k = CString::new(key).unwrap();
+            if k.is_empty() {
+                return Err("key should be passed");
             }
-        }
-        Ok(())
+            let v_subkeys_iter = subkeys.iter();
+            for skey in v_subkeys_iter {
+                let sk = CString::new(*skey).unwrap();
+                let result = unsafe { k2h_remove_str_subkey(self.handle, k.as_ptr(), sk.as_ptr()) };
+                if result == false {
+                    return Err("Failed to h.k2h_remove_str_all");
+                }
+                self.notify(|l| l.on_remove_subkey(key, skey));
+            }
+            Ok(())
+        })
     }
 
     /// Rename a old key with a new key.
     pub fn rename(&self, oldkey: &str, newkey: &str) -> Result<(), &'static str> {
-        let okey = CString::new(oldkey).unwrap();
-        if okey.is_empty() {
-            return Err("oldkey should be passed");
-        }
-        let nkey = CString::new(newkey).unwrap();
-        if nkey.is_empty() {
-            return Err("newkey should be passed");
-        }
-        let result = unsafe { k2h_rename_str(self.handle, okey.as_ptr(), nkey.as_ptr()) };
-        if result {
-            Ok(())
-        } else {
-            Err("Failed to h.k2h_rename")
-        }
+        instrument::observe("rename", Some(oldkey.len()), || {
+            let okey = CString::new(oldkey).unwrap();
+            if okey.is_empty() {
+                return Err("oldkey should be passed");
+            }
+            let nkey = CString::new(newkey).unwrap();
+            if nkey.is_empty() {
+                return Err("newkey should be passed");
+            }
+            let result = unsafe { k2h_rename_str(self.handle, okey.as_ptr(), nkey.as_ptr()) };
+            if result {
+                self.notify(|l| l.on_rename(oldkey, newkey));
+                Ok(())
+            } else {
+                Err("Failed to h.k2h_rename")
+            }
+        })
     }
 
     /// Set attribute of a key in the K2hash database.
@@ -1808,50 +2747,176 @@ impl K2hash {
         attr_name: &str,
         attr_val: &str,
     ) -> Result<(), &'static str> {
-        let key = CString::new(key).unwrap();
-        if key.as_bytes().is_empty() {
-            return Err("key should be passed");
-        }
-        let k_length = key.as_bytes_with_nul().len();
+        instrument::observe("set_attribute", Some(key.len()), || {
+            let key = CString::new(key).unwrap();
+            if key.as_bytes().is_empty() {
+                return Err("key should be passed");
+            }
+            let k_length = key.as_bytes_with_nul().len();
 
-        let attr_name = CString::new(attr_name).unwrap();
-        if attr_name.as_bytes().is_empty() {
-            return Err("attr_name should be passed");
-        }
-        let name_length = attr_name.as_bytes_with_nul().len();
+            let attr_name = CString::new(attr_name).unwrap();
+            if attr_name.as_bytes().is_empty() {
+                return Err("attr_name should be passed");
+            }
+            let name_length = attr_name.as_bytes_with_nul().len();
 
-        let attr_val = CString::new(attr_val).unwrap();
-        if attr_val.as_bytes().is_empty() {
-            return Err("attr_val should be passed");
-        }
-        let val_length = attr_val.as_bytes_with_nul().len();
+            let attr_val = CString::new(attr_val).unwrap();
+            if attr_val.as_bytes().is_empty() {
+                return Err("attr_val should be passed");
+            }
+            let val_length = attr_val.as_bytes_with_nul().len();
 
-        let result = unsafe {
-            k2h_add_attr(
-                self.handle,
-                key.as_bytes_with_nul().as_ptr(),
-                k_length,
-                attr_name.as_bytes_with_nul().as_ptr(),
-                name_length,
-                attr_val.as_bytes_with_nul().as_ptr(),
-                val_length,
-            )
-        };
-        if result {
-            Ok(())
-        } else {
-            Err("Failed to h.k2h_set_attribute")
-        }
+            let result = unsafe {
+                k2h_add_attr(
+                    self.handle,
+                    key.as_bytes_with_nul().as_ptr(),
+                    k_length,
+                    attr_name.as_bytes_with_nul().as_ptr(),
+                    name_length,
+                    attr_val.as_bytes_with_nul().as_ptr(),
+                    val_length,
+                )
+            };
+            if result {
+                self.notify(|l| {
+                    l.on_set_attribute(
+                        key.to_str().unwrap(),
+                        attr_name.to_str().unwrap(),
+                        attr_val.to_str().unwrap(),
+                    )
+                });
+                Ok(())
+            } else {
+                Err("Failed to h.k2h_set_attribute")
+            }
+        })
     }
 
-    /// Set a default encryption password.
-    pub fn set_default_encryption_password(&self, password: &str) -> Result<(), &'static str> {
-        let p = CString::new(password).unwrap();
-        if p.is_empty() {
-            return Err("password should be passed");
+    /// Remove a custom attribute from a key.
+    ///
+    /// Returns false if the key has no such attribute. Builtin attributes can not be removed.
+    pub fn remove_attribute(&self, key: &str, attr_name: &str) -> Result<bool, &'static str> {
+        instrument::observe("remove_attribute", Some(key.len()), || {
+            if attr_name.is_empty() {
+                return Err("attr_name should be passed");
+            }
+            if is_builtin_attribute(attr_name.as_bytes()) {
+                return Err("builtin attributes can not be changed");
+            }
+            let changed = self.rewrite_attributes(key, |attrs| {
+                attrs.retain(|(name, _)| attribute_name(name) != attr_name.as_bytes())
+            })?;
+            if changed {
+                self.notify(|l| l.on_remove_attribute(key, attr_name));
+            }
+            Ok(changed)
+        })
+    }
+
+    /// Replace the value of a custom attribute of a key, adding it if the key has no such attribute.
+    ///
+    /// Unlike `set_attribute`, the key keeps a single attribute of the name.
+    pub fn replace_attribute(
+        &self,
+        key: &str,
+        attr_name: &str,
+        attr_val: &str,
+    ) -> Result<(), &'static str> {
+        instrument::observe("replace_attribute", Some(key.len()), || {
+            if attr_name.is_empty() {
+                return Err("attr_name should be passed");
+            }
+            if attr_val.is_empty() {
+                return Err("attr_val should be passed");
+            }
+            if is_builtin_attribute(attr_name.as_bytes()) {
+                return Err("builtin attributes can not be changed");
+            }
+            let name = CString::new(attr_name).unwrap();
+            let val = CString::new(attr_val).unwrap();
+            let changed = self.rewrite_attributes(key, |attrs| {
+                attrs.retain(|(n, _)| attribute_name(n) != attr_name.as_bytes());
+                attrs.push((
+                    name.as_bytes_with_nul().to_vec(),
+                    val.as_bytes_with_nul().to_vec(),
+                ));
+            })?;
+            if changed {
+                self.notify(|l| l.on_set_attribute(key, attr_name, attr_val));
+            }
+            Ok(())
+        })
+    }
+
+    /// Remove all custom attributes from a key, keeping the builtin ones.
+    ///
+    /// Returns the number of removed attributes.
+    pub fn clear_custom_attributes(&self, key: &str) -> Result<usize, &'static str> {
+        instrument::observe("clear_custom_attributes", Some(key.len()), || {
+            let mut removed = Vec::new();
+            self.rewrite_attributes(key, |attrs| {
+                let (builtin, custom) = attrs
+                    .drain(..)
+                    .partition(|(name, _)| is_builtin_attribute(name));
+                *attrs = builtin;
+                removed = custom;
+            })?;
+            for (name, _) in &removed {
+                let name = String::from_utf8_lossy(attribute_name(name));
+                self.notify(|l| l.on_remove_attribute(key, &name));
+            }
+            Ok(removed.len())
+        })
+    }
+
+    /// Rewrites a key with its value, subkeys and the attributes updated by `f`.
+    ///
+    /// The C-library has no call to remove or replace a single attribute, so the key is read and
+    /// written back by k2h_set_all. k2h_set_all stores the attributes as passed without running
+    /// the builtin attribute hooks, so the value stays encrypted as stored and the mtime, history
+    /// and expire attributes are kept rather than renewed. Rewrites through this handle are
+    /// serialized, but a write to the key by another handle or process between the read and the
+    /// write is lost. Returns false if `f` changed nothing.
+    fn rewrite_attributes<F: FnOnce(&mut Vec<(Vec<u8>, Vec<u8>)>)>(
+        &self,
+        key: &str,
+        f: F,
+    ) -> Result<bool, &'static str> {
+        let k = CString::new(key).unwrap();
+        if k.is_empty() {
+            return Err("key should be passed");
+        }
+        let k = k.as_bytes_with_nul();
+        let _guard = self.attr_lock.lock().unwrap();
+        let value = read_value_raw(self.handle, k).ok_or("key does not exist")?;
+        let current = read_attributes_raw(self.handle, k);
+        let mut attrs = current.clone();
+        f(&mut attrs);
+        if attrs == current {
+            return Ok(false);
+        }
+        let subkeys = read_subkeys_raw(self.handle, k);
+        if set_all_raw(self.handle, k, &value, &subkeys, &attrs) {
+            Ok(true)
+        } else {
+            Err("k2h_set_all returns error")
+        }
+    }
+
+    /// Set a default encryption password.
+    pub fn set_default_encryption_password(&self, password: &str) -> Result<(), &'static str> {
+        let p = CString::new(password).unwrap();
+        if p.is_empty() {
+            return Err("password should be passed");
         }
         let result = unsafe { k2h_add_attr_crypt_pass(self.handle, p.as_ptr(), true) };
         if result {
+            let password = p.to_string_lossy().into_owned();
+            self.settings
+                .lock()
+                .unwrap()
+                .passwords
+                .push((password, true));
             Ok(())
         } else {
             Err("Failed to set value")
@@ -1871,61 +2936,108 @@ impl K2hash {
         password: Option<String>,
         expire_duration: Option<u64>,
     ) -> Result<(), &'static str> {
-        let k = CString::new(key).unwrap();
-        if k.is_empty() {
-            return Err("key should be passed");
-        }
-        let k_length = k.as_bytes_with_nul().len();
-        // Option<String> --as_ref()--> Option<&String> --CString::new()-> Option<CString>
-        let pass = password.as_ref().map(|p| CString::new(p.as_str()).unwrap());
-        // Option<CString> -> const unsigned char*
-        let c_pass = pass.as_ref().map_or(ptr::null(), |p| p.as_ptr());
-        // Option<usize>
-        let expire = expire_duration.map(|e| e as c_ulonglong);
-        let c_expire = expire
-            .as_ref()
-            .map_or(ptr::null(), |e| e as *const c_ulonglong);
-
-        let v_subkeys_iter = subkeys.iter();
-        for (subk, subv) in v_subkeys_iter {
-            let sk = CString::new(*subk).unwrap();
-            let sv = CString::new(*subv).unwrap();
-            if sk.as_bytes().is_empty() {
-                return Err("subkey's key should be passed");
-            }
-            let sk_length = sk.as_bytes_with_nul().len();
-            if sv.as_bytes().is_empty() {
-                return Err("subkey's val should be passed");
+        instrument::observe("set_subkeys", Some(key.len()), || {
+            let k = CString::new(key).unwrap();
+            if k.is_empty() {
+                return Err("key should be passed");
             }
-            let sv_length = sv.as_bytes_with_nul().len();
-            let result = unsafe {
-                k2h_add_subkey_wa(
-                    self.handle,
-                    k.as_bytes_with_nul().as_ptr(),
-                    k_length,
-                    sk.as_bytes_with_nul().as_ptr(),
-                    sk_length,
-                    sv.as_bytes_with_nul().as_ptr(),
-                    sv_length,
-                    c_pass,
-                    c_expire,
-                )
-            };
-            if result == false {
-                return Err("Failed to h.k2h_remove_str_all");
+            let k_length = k.as_bytes_with_nul().len();
+            // Option<String> --as_ref()--> Option<&String> --CString::new()-> Option<CString>
+            let pass = password.as_ref().map(|p| CString::new(p.as_str()).unwrap());
+            // Option<CString> -> const unsigned char*
+            let c_pass = pass.as_ref().map_or(ptr::null(), |p| p.as_ptr());
+            // Option<usize>
+            let expire = expire_duration.map(|e| e as c_ulonglong);
+            let c_expire = expire
+                .as_ref()
+                .map_or(ptr::null(), |e| e as *const c_ulonglong);
+
+            let v_subkeys_iter = subkeys.iter();
+            for (subk, subv) in v_subkeys_iter {
+                let sk = CString::new(*subk).unwrap();
+                let sv = CString::new(*subv).unwrap();
+                if sk.as_bytes().is_empty() {
+                    return Err("subkey's key should be passed");
+                }
+                let sk_length = sk.as_bytes_with_nul().len();
+                if sv.as_bytes().is_empty() {
+                    return Err("subkey's val should be passed");
+                }
+                let sv_length = sv.as_bytes_with_nul().len();
+                let result = unsafe {
+                    k2h_add_subkey_wa(
+                        self.handle,
+                        k.as_bytes_with_nul().as_ptr(),
+                        k_length,
+                        sk.as_bytes_with_nul().as_ptr(),
+                        sk_length,
+                        sv.as_bytes_with_nul().as_ptr(),
+                        sv_length,
+                        c_pass,
+                        c_expire,
+                    )
+                };
+                if result == false {
+                    return Err("Failed to h.k2h_remove_str_all");
+                }
+                self.notify(|l| l.on_add_subkey(key, subk, subv));
             }
+            Ok(())
+        })
+    }
+
+    /// Set a listener which is notified of the changes made through this handle.
+    ///
+    /// The listener replaces the previous one.
+    pub fn set_tx_listener<L: TxListener + 'static>(&self, listener: L) {
+        *self.listener.write().unwrap() = Some(Arc::new(listener));
+    }
+
+    /// Remove the listener set by `set_tx_listener`.
+    pub fn clear_tx_listener(&self) {
+        *self.listener.write().unwrap() = None;
+    }
+
+    /// Calls the listener if it is set.
+    fn notify<F: FnOnce(&dyn TxListener)>(&self, f: F) {
+        // clone the listener so that it can replace itself without a deadlock.
+        let listener = self.listener.read().unwrap().clone();
+        if let Some(listener) = listener {
+            f(listener.as_ref());
         }
-        Ok(())
+    }
+
+    /// Get the statistics of the K2hash database.
+    pub fn stats(&self) -> Result<K2hashStats, &'static str> {
+        let state = unsafe { k2h_get_state(self.handle) };
+        if state.is_null() {
+            return Err("k2h_get_state returns error");
+        }
+        let stats = K2hashStats::from(unsafe { &*state });
+        unsafe { libc::free(state as *mut c_void) };
+        Ok(stats)
     }
 
     /// Stop a transaction.
     pub fn stop_tx(&self) -> Result<(), &'static str> {
-        let result = unsafe { k2h_disable_transaction(self.handle) };
-        if result {
-            Ok(())
-        } else {
-            Err("k2h_disable_transaction returns error")
-        }
+        instrument::observe("stop_tx", None, || {
+            let result = unsafe { k2h_disable_transaction(self.handle) };
+            if result {
+                Ok(())
+            } else {
+                Err("k2h_disable_transaction returns error")
+            }
+        })
+    }
+
+    /// Start a transaction which is stopped when the returned guard is dropped.
+    pub fn transaction(&self, txfile: &str) -> Result<TxGuard<'_>, &'static str> {
+        self.transaction_builder(txfile).begin()
+    }
+
+    /// Create a builder of a transaction with options.
+    pub fn transaction_builder(&self, txfile: &str) -> TxGuardBuilder<'_> {
+        TxGuardBuilder::new(self, txfile)
     }
 
     /// Print the k2hash C-library version.
@@ -1934,6 +3046,22 @@ impl K2hash {
         Ok(())
     }
 
+    /// Get the k2hash C-library version.
+    ///
+    /// The version is parsed from the output of `k2h_print_version` once and cached. An output
+    /// without a `major.minor.patch` version is an error.
+    pub fn library_version() -> Result<Version, &'static str> {
+        static VERSION: OnceLock<Option<Version>> = OnceLock::new();
+        let version = VERSION.get_or_init(|| {
+            let mut banner = Vec::new();
+            K2hash::version_to(&mut banner).ok()?;
+            parse_version_banner(&String::from_utf8_lossy(&banner))
+        });
+        version
+            .clone()
+            .ok_or("Failed to parse the k2hash C-library version")
+    }
+
     /// Set transaction pool size.
     pub fn set_tx_pool_size(size: i32) -> Result<(), &'static str> {
         if size < 0 {
@@ -1948,6 +3076,7 @@ impl K2hash {
     }
 }
 
+#[cfg(feature = "libk2hash")]
 impl Drop for K2hash {
     fn drop(&mut self) {
         unsafe {
@@ -1956,15 +3085,237 @@ impl Drop for K2hash {
     }
 }
 
+/// TxListener receives the changes made through a K2hash handle.
+///
+/// Every method is called after the change succeeded and does nothing by default. Only the
+/// K2hash methods are observed, so changes by queues, `apply_tx_log` or other processes are
+/// not notified. Values are passed as given to the K2hash method, before encryption.
+///
+/// # Examples
+///
+/// ```
+/// use k2hash_rust::{K2hash, TxListener};
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+/// use std::sync::Arc;
+/// struct Counter(Arc<AtomicUsize>);
+/// impl TxListener for Counter {
+///     fn on_set(&self, _key: &str, _value: &str) {
+///         self.0.fetch_add(1, Ordering::Relaxed);
+///     }
+/// }
+/// let db = K2hash::open_mem().expect("open_mem failed");
+/// let count = Arc::new(AtomicUsize::new(0));
+/// db.set_tx_listener(Counter(count.clone()));
+/// assert!(db.set("hello", "world").is_ok(), "Set operation failed");
+/// assert_eq!(count.load(Ordering::Relaxed), 1, "on_set should be called once");
+/// ```
+#[cfg(feature = "libk2hash")]
+pub trait TxListener: Send + Sync {
+    /// Called after a value is set to a key.
+    fn on_set(&self, _key: &str, _value: &str) {}
+    /// Called after a key is removed.
+    fn on_remove(&self, _key: &str) {}
+    /// Called after a key is renamed.
+    fn on_rename(&self, _oldkey: &str, _newkey: &str) {}
+    /// Called after a subkey is added to a key.
+    fn on_add_subkey(&self, _key: &str, _subkey: &str, _subval: &str) {}
+    /// Called after a subkey is removed from a key.
+    fn on_remove_subkey(&self, _key: &str, _subkey: &str) {}
+    /// Called after an attribute is set to a key.
+    fn on_set_attribute(&self, _key: &str, _attr_name: &str, _attr_val: &str) {}
+    /// Called after an attribute is removed from a key.
+    fn on_remove_attribute(&self, _key: &str, _attr_name: &str) {}
+}
+
+/// The attribute name of the failed delivery attempts of a queued value.
+#[cfg(feature = "libk2hash")]
+const ATTEMPTS_ATTR: &str = "attempts";
+
+/// TxGuard keeps the transaction log of a K2hash database enabled while it is alive.
+///
+/// The transaction log is disabled when the guard is dropped, even on an early return or a panic.
+///
+/// # Examples
+///
+/// ```
+/// use k2hash_rust::K2hash;
+/// let db = K2hash::open_mem().expect("open_mem failed");
+/// {
+///     let tx = db.transaction("test.log").expect("Begin transaction operation failed");
+///     assert!(tx.fd().is_ok(), "Transaction file descriptor should be valid");
+///     assert!(db.set("hello", "world").is_ok(), "Set operation failed");
+/// } // the transaction log is disabled here.
+/// ```
+#[cfg(feature = "libk2hash")]
+pub struct TxGuard<'a> {
+    db: &'a K2hash,
+    active: bool,
+}
+
+#[cfg(feature = "libk2hash")]
+impl TxGuard<'_> {
+    /// Get the file descriptor of the transaction archive which is owned by k2hash.
+    pub fn fd(&self) -> Result<BorrowedFd<'_>, &'static str> {
+        let fd = self.db.get_tx_file_fd()?;
+        // k2hash keeps the fd open until the transaction is disabled by this guard.
+        Ok(unsafe { BorrowedFd::borrow_raw(fd) })
+    }
+
+    /// Get a duplicated file descriptor of the transaction archive.
+    pub fn try_clone_fd(&self) -> Result<OwnedFd, &'static str> {
+        let fd = self.db.get_tx_file_fd()?;
+        let dup = unsafe { libc::dup(fd) };
+        if dup < 0 {
+            Err("Failed to duplicate the transaction file descriptor")
+        } else {
+            Ok(unsafe { OwnedFd::from_raw_fd(dup) })
+        }
+    }
+
+    /// Stop the transaction and report an error if k2hash fails to disable it.
+    pub fn finish(mut self) -> Result<(), &'static str> {
+        self.active = false;
+        self.db.stop_tx()
+    }
+}
+
+#[cfg(feature = "libk2hash")]
+impl Drop for TxGuard<'_> {
+    fn drop(&mut self) {
+        if self.active {
+            let _ = self.db.stop_tx();
+        }
+    }
+}
+
+/// TxGuardBuilder provides a builder pattern for starting a transaction.
+///
+/// # Examples
+///
+/// ```
+/// use k2hash_rust::K2hash;
+/// let db = K2hash::open_mem().expect("open_mem failed");
+/// let tx = db
+///     .transaction_builder("test.log")
+///     .prefix("hello".to_string()) // Optional key prefix to log
+///     .param("param".to_string()) // Optional parameter
+///     .expire_duration(60) // Optional expiration duration
+///     .begin()
+///     .expect("Begin transaction operation failed");
+/// assert!(tx.finish().is_ok(), "Stop transaction operation failed");
+/// ```
+#[cfg(feature = "libk2hash")]
+pub struct TxGuardBuilder<'a> {
+    db: &'a K2hash,
+    txfile: String,
+    prefix: Option<String>,
+    param: Option<String>,
+    expire_duration: Option<u64>,
+}
+
+#[cfg(feature = "libk2hash")]
+impl<'a> TxGuardBuilder<'a> {
+    /// Create a new TxGuardBuilder instance.
+    pub fn new(db: &'a K2hash, txfile: &str) -> Self {
+        TxGuardBuilder {
+            db,
+            txfile: txfile.to_string(),
+            prefix: None,
+            param: None,
+            expire_duration: None,
+        }
+    }
+
+    /// Set the key prefix of the transaction.
+    pub fn prefix(mut self, prefix: String) -> Self {
+        self.prefix = Some(prefix);
+        self
+    }
+    /// Set the parameter of the transaction.
+    pub fn param(mut self, param: String) -> Self {
+        self.param = Some(param);
+        self
+    }
+    /// Set the expiration duration of the transaction.
+    pub fn expire_duration(mut self, expire_duration: u64) -> Self {
+        self.expire_duration = Some(expire_duration);
+        self
+    }
+    /// Start the transaction.
+    pub fn begin(self) -> Result<TxGuard<'a>, &'static str> {
+        self.db.begin_tx_with_options(
+            &self.txfile,
+            self.prefix,
+            self.param,
+            self.expire_duration,
+        )?;
+        Ok(TxGuard {
+            db: self.db,
+            active: true,
+        })
+    }
+}
+
+/// The key whose subkeys register the queues opened through this crate for `K2hash::list_queues`.
+///
+/// A subkey is named `{QUEUE_REGISTRY}.{kind}.{fifo|lifo}.{prefix}`.
+#[cfg(feature = "libk2hash")]
+pub const QUEUE_REGISTRY: &str = "k2hash_rust.queues";
+
+/// Registers a queue in `QUEUE_REGISTRY` unless it is already registered.
+///
+/// The registry only serves `K2hash::list_queues`, so a failure does not fail the constructor.
+#[cfg(feature = "libk2hash")]
+fn register_queue(k2h: u64, kind: QueueKind, fifo: bool, prefix: Option<&str>) {
+    let registry = CString::new(QUEUE_REGISTRY).unwrap();
+    let mode = if fifo { "fifo" } else { "lifo" };
+    let Ok(entry) = CString::new(format!(
+        "{}.{}.{}.{}",
+        QUEUE_REGISTRY,
+        kind.name(),
+        mode,
+        prefix.unwrap_or("")
+    )) else {
+        return;
+    };
+    let registry = registry.as_bytes_with_nul();
+    let entry = entry.as_bytes_with_nul();
+    if read_subkeys_raw(k2h, registry).iter().any(|k| k == entry) {
+        return;
+    }
+    // the subkeys need a parent key.
+    if read_value_raw(k2h, registry).is_none() && !set_all_raw(k2h, registry, b"\0", &[], &[]) {
+        return;
+    }
+    let val = b"\0";
+    unsafe {
+        k2h_add_subkey_wa(
+            k2h,
+            registry.as_ptr(),
+            registry.len(),
+            entry.as_ptr(),
+            entry.len(),
+            val.as_ptr(),
+            val.len(),
+            ptr::null(),
+            ptr::null(),
+        )
+    };
+}
+
 /// Base struct of Queue and KeyQueue struct.
+#[cfg(feature = "libk2hash")]
 pub struct BaseQueue {
     k2h: u64,
     fifo: bool,
     prefix: Option<String>,
     password: Option<String>,
     expire_duration: Option<u64>,
+    max_attempts: Option<u32>,
     handle: u64,
+    depth: instrument::DepthSampler,
 }
+#[cfg(feature = "libk2hash")]
 impl BaseQueue {
     /// Create a new BaseQueue.
     pub fn new(
@@ -1980,7 +3331,9 @@ impl BaseQueue {
             prefix,
             password,
             expire_duration,
+            max_attempts: None,
             handle: 0,
+            depth: instrument::DepthSampler::default(),
         }
     }
 
@@ -1988,6 +3341,26 @@ impl BaseQueue {
     pub fn handle(&self) -> u64 {
         self.handle
     }
+
+    /// Get the prefix of the dead-letter queue.
+    fn dead_letter_prefix(&self) -> String {
+        match self.prefix {
+            Some(ref p) => format!("{}.dlq.", p),
+            None => "dlq.".to_string(),
+        }
+    }
+
+    /// Record the depth of the queue when a sample is due.
+    fn record_depth(&self) {
+        self.depth.record(self.prefix.as_deref(), || unsafe {
+            k2h_q_count(self.handle) as usize
+        });
+    }
+
+    /// Check if the delivery attempts reached the max attempts.
+    fn is_dead(&self, attempts: u32) -> bool {
+        self.max_attempts.is_some_and(|max| attempts >= max)
+    }
 }
 
 /// Queue provides FIFO (first-in, first-out) functionality using k2hash database.
@@ -2012,6 +3385,7 @@ impl BaseQueue {
 ///     panic!("Get operation failed or returned None");
 /// }
 /// ```
+#[cfg(feature = "libk2hash")]
 pub struct Queue {
     base: BaseQueue,
 }
@@ -2039,9 +3413,11 @@ pub struct Queue {
 ///     panic!("Get operation failed or returned None");
 /// }
 /// ```
+#[cfg(feature = "libk2hash")]
 pub struct KeyQueue {
     base: BaseQueue,
 }
+#[cfg(feature = "libk2hash")]
 impl Queue {
     /// Create a new Queue.
     pub fn new(
@@ -2061,6 +3437,12 @@ impl Queue {
         if base.handle == 0 {
             Err("Queue instance failed")
         } else {
+            register_queue(
+                base.k2h,
+                QueueKind::Queue,
+                base.fifo,
+                base.prefix.as_deref(),
+            );
             Ok(Queue { base })
         }
     }
@@ -2072,55 +3454,63 @@ impl Queue {
 
     /// Put a value into the Queue.
     pub fn put(&self, value: &str) -> Result<(), &'static str> {
-        let c_val = CString::new(value).unwrap();
-        let c_pass = self
-            .base
-            .password
-            .as_ref()
-            .map(|p| CString::new(p.as_str()).unwrap());
-        let c_pattrspck = ptr::null();
-        let c_attrspckcnt = 0;
-        let expire = self.base.expire_duration.map(|e| e as c_ulonglong);
-        let result = unsafe {
-            k2h_q_str_push_wa(
-                self.base.handle,
-                c_val.as_ptr(),
-                c_pattrspck,
-                c_attrspckcnt,
-                c_pass.as_ref().map_or(ptr::null(), |p| p.as_ptr()),
-                expire
-                    .as_ref()
-                    .map_or(ptr::null(), |e| e as *const c_ulonglong),
-            )
-        };
-        if result {
-            Ok(())
-        } else {
-            Err("Failed to set value")
-        }
+        let result = instrument::observe("queue_push", None, || {
+            let c_val = CString::new(value).unwrap();
+            let c_pass = self
+                .base
+                .password
+                .as_ref()
+                .map(|p| CString::new(p.as_str()).unwrap());
+            let c_pattrspck = ptr::null();
+            let c_attrspckcnt = 0;
+            let expire = self.base.expire_duration.map(|e| e as c_ulonglong);
+            let result = unsafe {
+                k2h_q_str_push_wa(
+                    self.base.handle,
+                    c_val.as_ptr(),
+                    c_pattrspck,
+                    c_attrspckcnt,
+                    c_pass.as_ref().map_or(ptr::null(), |p| p.as_ptr()),
+                    expire
+                        .as_ref()
+                        .map_or(ptr::null(), |e| e as *const c_ulonglong),
+                )
+            };
+            if result {
+                Ok(())
+            } else {
+                Err("Failed to set value")
+            }
+        });
+        self.base.record_depth();
+        result
     }
 
     /// Get a value from the Queue.
     pub fn get(&self) -> Option<String> {
-        let mut val_ptr: *mut c_char = ptr::null_mut();
-        let c_pass = self
-            .base
-            .password
-            .as_ref()
-            .map(|p| CString::new(p.as_str()).unwrap());
-        let result = unsafe {
-            k2h_q_str_pop_wp(
-                self.base.handle,
-                &mut val_ptr,
-                c_pass.as_ref().map_or(ptr::null(), |p| p.as_ptr()),
-            )
-        };
-        if result && !val_ptr.is_null() {
-            let cstr = unsafe { CStr::from_ptr(val_ptr) };
-            Some(cstr.to_string_lossy().into_owned())
-        } else {
-            None
-        }
+        let result = instrument::observe("queue_pop", None, || {
+            let mut val_ptr: *mut c_char = ptr::null_mut();
+            let c_pass = self
+                .base
+                .password
+                .as_ref()
+                .map(|p| CString::new(p.as_str()).unwrap());
+            let result = unsafe {
+                k2h_q_str_pop_wp(
+                    self.base.handle,
+                    &mut val_ptr,
+                    c_pass.as_ref().map_or(ptr::null(), |p| p.as_ptr()),
+                )
+            };
+            if result && !val_ptr.is_null() {
+                let cstr = unsafe { CStr::from_ptr(val_ptr) };
+                Some(cstr.to_string_lossy().into_owned())
+            } else {
+                None
+            }
+        });
+        self.base.record_depth();
+        result
     }
 
     /// Get the size of the Queue.
@@ -2167,34 +3557,55 @@ impl Queue {
         }
     }
 
+    /// Write the objects in the Queue to the writer.
+    pub fn dump_to<W: Write>(&self, writer: &mut W) -> Result<(), &'static str> {
+        let result = write_stream_to(writer, |stream| unsafe {
+            k2h_q_dump(self.base.handle, stream)
+        })?;
+        if result {
+            Ok(())
+        } else {
+            Err("k2h_q_dump returns error")
+        }
+    }
+
+    /// Get the objects in the Queue as a string.
+    pub fn dump_string(&self) -> Result<String, &'static str> {
+        let mut output = Vec::new();
+        self.dump_to(&mut output)?;
+        Ok(String::from_utf8_lossy(&output).into_owned())
+    }
+
     /// Remove the objects from the Queue.
     pub fn remove(&self, count: usize) -> Result<Vec<String>, &'static str> {
-        if count == 0 {
-            return Ok(Vec::new());
-        }
-        let mut vals = Vec::new();
-        for _ in 0..count {
-            let mut val_ptr: *mut c_char = ptr::null_mut();
-            let c_pass = self
-                .base
-                .password
-                .as_ref()
-                .map(|p| CString::new(p.as_str()).unwrap());
-            let result = unsafe {
-                k2h_q_str_pop_wp(
-                    self.base.handle,
-                    &mut val_ptr,
-                    c_pass.as_ref().map_or(ptr::null(), |p| p.as_ptr()),
-                )
-            };
-            if result && !val_ptr.is_null() {
-                let cstr = unsafe { CStr::from_ptr(val_ptr) };
-                vals.push(cstr.to_string_lossy().into_owned());
-            } else {
-                break; // Stop if no more elements are available
+        instrument::observe("queue_remove", None, || {
+            if count == 0 {
+                return Ok(Vec::new());
             }
-        }
-        Ok(vals)
+            let mut vals = Vec::new();
+            for _ in 0..count {
+                let mut val_ptr: *mut c_char = ptr::null_mut();
+                let c_pass = self
+                    .base
+                    .password
+                    .as_ref()
+                    .map(|p| CString::new(p.as_str()).unwrap());
+                let result = unsafe {
+                    k2h_q_str_pop_wp(
+                        self.base.handle,
+                        &mut val_ptr,
+                        c_pass.as_ref().map_or(ptr::null(), |p| p.as_ptr()),
+                    )
+                };
+                if result && !val_ptr.is_null() {
+                    let cstr = unsafe { CStr::from_ptr(val_ptr) };
+                    vals.push(cstr.to_string_lossy().into_owned());
+                } else {
+                    break; // Stop if no more elements are available
+                }
+            }
+            Ok(vals)
+        })
     }
 
     /// Remove the objects from the Queue.
@@ -2211,6 +3622,110 @@ impl Queue {
     pub fn close(&self) -> bool {
         unsafe { k2h_q_free(self.base.handle) }
     }
+
+    /// Put a value with the number of failed delivery attempts into the Queue.
+    fn put_with_attempts(&self, value: &str, attempts: u32) -> Result<(), &'static str> {
+        let c_val = CString::new(value).unwrap();
+        let c_pass = self
+            .base
+            .password
+            .as_ref()
+            .map(|p| CString::new(p.as_str()).unwrap());
+        let attr_name = CString::new(ATTEMPTS_ATTR).unwrap();
+        let attr_val = CString::new(attempts.to_string()).unwrap();
+        let attr = K2hAttrPack {
+            pkey: attr_name.as_ptr() as *mut u8,
+            keylength: attr_name.as_bytes_with_nul().len(),
+            pval: attr_val.as_ptr() as *mut u8,
+            vallength: attr_val.as_bytes_with_nul().len(),
+        };
+        let expire = self.base.expire_duration.map(|e| e as c_ulonglong);
+        let result = unsafe {
+            k2h_q_str_push_wa(
+                self.base.handle,
+                c_val.as_ptr(),
+                &attr as *const K2hAttrPack as *const c_void,
+                1,
+                c_pass.as_ref().map_or(ptr::null(), |p| p.as_ptr()),
+                expire
+                    .as_ref()
+                    .map_or(ptr::null(), |e| e as *const c_ulonglong),
+            )
+        };
+        if result {
+            Ok(())
+        } else {
+            Err("Failed to set value")
+        }
+    }
+
+    /// Get a value and the number of its failed delivery attempts from the Queue.
+    pub fn get_with_attempts(&self) -> Option<(String, u32)> {
+        let mut val_ptr: *mut c_char = ptr::null_mut();
+        let mut attrs_ptr: *mut c_void = ptr::null_mut();
+        let mut attrs_cnt: c_int = 0;
+        let c_pass = self
+            .base
+            .password
+            .as_ref()
+            .map(|p| CString::new(p.as_str()).unwrap());
+        let result = unsafe {
+            k2h_q_str_pop_wa(
+                self.base.handle,
+                &mut val_ptr,
+                &mut attrs_ptr,
+                &mut attrs_cnt,
+                c_pass.as_ref().map_or(ptr::null(), |p| p.as_ptr()),
+            )
+        };
+        let mut attempts = 0;
+        if !attrs_ptr.is_null() {
+            let attr_name = CString::new(ATTEMPTS_ATTR).unwrap();
+            for i in 0..attrs_cnt.max(0) as usize {
+                let attr_pack: &K2hAttrPack = unsafe { &*(attrs_ptr as *const K2hAttrPack).add(i) };
+                let name =
+                    unsafe { std::slice::from_raw_parts(attr_pack.pkey, attr_pack.keylength) };
+                if name == attr_name.as_bytes_with_nul() {
+                    let val = unsafe { CStr::from_ptr(attr_pack.pval as *const c_char) };
+                    attempts = val.to_string_lossy().parse().unwrap_or(0);
+                }
+            }
+            unsafe { k2h_free_attrpack(attrs_ptr, attrs_cnt) };
+        }
+        if result && !val_ptr.is_null() {
+            let cstr = unsafe { CStr::from_ptr(val_ptr) };
+            Some((cstr.to_string_lossy().into_owned(), attempts))
+        } else {
+            None
+        }
+    }
+
+    /// Report a failed delivery of a value taken by `get_with_attempts`.
+    ///
+    /// The value is put back with one more attempt, or moved to the dead-letter queue
+    /// when the attempts reach `max_attempts`. Returns true if the value is dead-lettered.
+    pub fn nack(&self, value: &str, attempts: u32) -> Result<bool, &'static str> {
+        let attempts = attempts.saturating_add(1);
+        if self.base.is_dead(attempts) {
+            let dlq = self.dead_letters()?;
+            let result = dlq.put(value);
+            dlq.close();
+            result.map(|_| true)
+        } else {
+            self.put_with_attempts(value, attempts).map(|_| false)
+        }
+    }
+
+    /// Get the dead-letter queue of the Queue, which has the password and the expire of the Queue.
+    pub fn dead_letters(&self) -> Result<Queue, &'static str> {
+        Queue::new(
+            self.base.k2h,
+            self.base.fifo,
+            Some(self.base.dead_letter_prefix()),
+            self.base.password.clone(),
+            self.base.expire_duration,
+        )
+    }
 }
 
 /// QueueBuilder provides a builder pattern for creating Queue instances.
@@ -2237,14 +3752,17 @@ impl Queue {
 /// // check if queue is not null
 /// assert!(qb1.handle() != 0, "Queue handle should not be zero");
 /// ```
+#[cfg(feature = "libk2hash")]
 pub struct QueueBuilder {
     k2h: u64,
     fifo: bool,
     prefix: Option<String>,
     password: Option<String>,
     expire_duration: Option<u64>,
+    max_attempts: Option<u32>,
 }
 
+#[cfg(feature = "libk2hash")]
 impl QueueBuilder {
     /// Create a new QueueBuilder instance.
     pub fn new(k2h: u64) -> Self {
@@ -2254,6 +3772,7 @@ impl QueueBuilder {
             prefix: None,
             password: None,
             expire_duration: None,
+            max_attempts: None,
         }
     }
 
@@ -2277,18 +3796,26 @@ impl QueueBuilder {
         self.expire_duration = Some(expire_duration);
         self
     }
+    /// Set the max delivery attempts before a value is moved to the dead-letter queue.
+    pub fn max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = Some(max_attempts);
+        self
+    }
     /// Build the Queue.
     pub fn build(self) -> Result<Queue, &'static str> {
-        Queue::new(
+        let mut q = Queue::new(
             self.k2h,
             self.fifo,
             self.prefix,
             self.password,
             self.expire_duration,
-        )
+        )?;
+        q.base.max_attempts = self.max_attempts;
+        Ok(q)
     }
 }
 
+#[cfg(feature = "libk2hash")]
 impl KeyQueue {
     /// Create a new KeyQueue instance.
     pub fn new(
@@ -2308,6 +3835,12 @@ impl KeyQueue {
         if base.handle == 0 {
             Err("Failed to create KeyQueue handle")
         } else {
+            register_queue(
+                base.k2h,
+                QueueKind::KeyQueue,
+                base.fifo,
+                base.prefix.as_deref(),
+            );
             Ok(KeyQueue { base })
         }
     }
@@ -2319,56 +3852,64 @@ impl KeyQueue {
 
     /// Put a key-value pair into the KeyQueue.
     pub fn put(&self, key: &str, value: &str) -> Result<(), &'static str> {
-        let c_key = CString::new(key).unwrap();
-        let c_val = CString::new(value).unwrap();
-        let c_pass = self
-            .base
-            .password
-            .as_ref()
-            .map(|p| CString::new(p.as_str()).unwrap());
-        let expire = self.base.expire_duration.map(|e| e as c_ulonglong);
-        let result = unsafe {
-            k2h_keyq_str_push_keyval_wa(
-                self.base.handle,
-                c_key.as_ptr(),
-                c_val.as_ptr(),
-                c_pass.as_ref().map_or(ptr::null(), |p| p.as_ptr()),
-                expire
-                    .as_ref()
-                    .map_or(ptr::null(), |e| e as *const c_ulonglong),
-            )
-        };
-        if result {
-            Ok(())
-        } else {
-            Err("Failed to set value")
-        }
+        let result = instrument::observe("keyqueue_push", Some(key.len()), || {
+            let c_key = CString::new(key).unwrap();
+            let c_val = CString::new(value).unwrap();
+            let c_pass = self
+                .base
+                .password
+                .as_ref()
+                .map(|p| CString::new(p.as_str()).unwrap());
+            let expire = self.base.expire_duration.map(|e| e as c_ulonglong);
+            let result = unsafe {
+                k2h_keyq_str_push_keyval_wa(
+                    self.base.handle,
+                    c_key.as_ptr(),
+                    c_val.as_ptr(),
+                    c_pass.as_ref().map_or(ptr::null(), |p| p.as_ptr()),
+                    expire
+                        .as_ref()
+                        .map_or(ptr::null(), |e| e as *const c_ulonglong),
+                )
+            };
+            if result {
+                Ok(())
+            } else {
+                Err("Failed to set value")
+            }
+        });
+        self.base.record_depth();
+        result
     }
 
     /// Get a key-value pair from the KeyQueue.
     pub fn get(&self) -> Option<(String, String)> {
-        let mut key_ptr: *mut c_char = ptr::null_mut();
-        let mut val_ptr: *mut c_char = ptr::null_mut();
-        let c_pass = self
-            .base
-            .password
-            .as_ref()
-            .map(|p| CString::new(p.as_str()).unwrap());
-        let result = unsafe {
-            k2h_keyq_str_pop_keyval_wp(
-                self.base.handle,
-                &mut key_ptr,
-                &mut val_ptr,
-                c_pass.as_ref().map_or(ptr::null(), |p| p.as_ptr()),
-            )
-        };
-        if result && !key_ptr.is_null() && !val_ptr.is_null() {
-            let key = unsafe { CStr::from_ptr(key_ptr).to_string_lossy().into_owned() };
-            let val = unsafe { CStr::from_ptr(val_ptr).to_string_lossy().into_owned() };
-            Some((key, val))
-        } else {
-            None
-        }
+        let result = instrument::observe("keyqueue_pop", None, || {
+            let mut key_ptr: *mut c_char = ptr::null_mut();
+            let mut val_ptr: *mut c_char = ptr::null_mut();
+            let c_pass = self
+                .base
+                .password
+                .as_ref()
+                .map(|p| CString::new(p.as_str()).unwrap());
+            let result = unsafe {
+                k2h_keyq_str_pop_keyval_wp(
+                    self.base.handle,
+                    &mut key_ptr,
+                    &mut val_ptr,
+                    c_pass.as_ref().map_or(ptr::null(), |p| p.as_ptr()),
+                )
+            };
+            if result && !key_ptr.is_null() && !val_ptr.is_null() {
+                let key = unsafe { CStr::from_ptr(key_ptr).to_string_lossy().into_owned() };
+                let val = unsafe { CStr::from_ptr(val_ptr).to_string_lossy().into_owned() };
+                Some((key, val))
+            } else {
+                None
+            }
+        });
+        self.base.record_depth();
+        result
     }
 
     /// Get the size of the KeyQueue.
@@ -2421,37 +3962,58 @@ impl KeyQueue {
         }
     }
 
+    /// Write the elements in the KeyQueue to the writer.
+    pub fn dump_to<W: Write>(&self, writer: &mut W) -> Result<(), &'static str> {
+        let result = write_stream_to(writer, |stream| unsafe {
+            k2h_keyq_dump(self.base.handle, stream)
+        })?;
+        if result {
+            Ok(())
+        } else {
+            Err("k2h_keyq_dump returns error")
+        }
+    }
+
+    /// Get the elements in the KeyQueue as a string.
+    pub fn dump_string(&self) -> Result<String, &'static str> {
+        let mut output = Vec::new();
+        self.dump_to(&mut output)?;
+        Ok(String::from_utf8_lossy(&output).into_owned())
+    }
+
     /// Remove elements from the KeyQueue.
     pub fn remove(&self, count: usize) -> Result<Vec<(String, String)>, &'static str> {
-        if count == 0 {
-            return Ok(Vec::new());
-        }
-        let mut vals = Vec::new();
-        for _ in 0..count {
-            let mut key_ptr: *mut c_char = ptr::null_mut();
-            let mut val_ptr: *mut c_char = ptr::null_mut();
-            let c_pass = self
-                .base
-                .password
-                .as_ref()
-                .map(|p| CString::new(p.as_str()).unwrap());
-            let result = unsafe {
-                k2h_keyq_str_pop_keyval_wp(
-                    self.base.handle,
-                    &mut key_ptr,
-                    &mut val_ptr,
-                    c_pass.as_ref().map_or(ptr::null(), |p| p.as_ptr()),
-                )
-            };
-            if result && !key_ptr.is_null() && !val_ptr.is_null() {
-                let key = unsafe { CStr::from_ptr(key_ptr).to_string_lossy().into_owned() };
-                let val = unsafe { CStr::from_ptr(val_ptr).to_string_lossy().into_owned() };
-                vals.push((key, val));
-            } else {
-                break; // Stop if no more elements are available
+        instrument::observe("keyqueue_remove", None, || {
+            if count == 0 {
+                return Ok(Vec::new());
             }
-        }
-        Ok(vals)
+            let mut vals = Vec::new();
+            for _ in 0..count {
+                let mut key_ptr: *mut c_char = ptr::null_mut();
+                let mut val_ptr: *mut c_char = ptr::null_mut();
+                let c_pass = self
+                    .base
+                    .password
+                    .as_ref()
+                    .map(|p| CString::new(p.as_str()).unwrap());
+                let result = unsafe {
+                    k2h_keyq_str_pop_keyval_wp(
+                        self.base.handle,
+                        &mut key_ptr,
+                        &mut val_ptr,
+                        c_pass.as_ref().map_or(ptr::null(), |p| p.as_ptr()),
+                    )
+                };
+                if result && !key_ptr.is_null() && !val_ptr.is_null() {
+                    let key = unsafe { CStr::from_ptr(key_ptr).to_string_lossy().into_owned() };
+                    let val = unsafe { CStr::from_ptr(val_ptr).to_string_lossy().into_owned() };
+                    vals.push((key, val));
+                } else {
+                    break; // Stop if no more elements are available
+                }
+            }
+            Ok(vals)
+        })
     }
 
     /// Remove all elements from the KeyQueue.
@@ -2468,6 +4030,60 @@ impl KeyQueue {
     pub fn close(&self) -> bool {
         unsafe { k2h_keyq_free(self.base.handle) }
     }
+
+    /// Get a key-value pair and the number of its failed delivery attempts from the KeyQueue.
+    ///
+    /// The attempts are an attribute of the queued key. It is read from the popped key and
+    /// cleared at once, so the count is carried by the caller until `nack` and a later `put`
+    /// of the same key starts from zero, whether the pair succeeds or is dead-lettered.
+    pub fn get_with_attempts(&self) -> Option<(String, String, u32)> {
+        let (key, val) = self.get()?;
+        let attempts = read_str_attribute(self.base.k2h, &key, ATTEMPTS_ATTR);
+        if attempts.is_some() {
+            replace_str_attribute(self.base.k2h, &key, ATTEMPTS_ATTR, None);
+        }
+        let attempts = attempts
+            .and_then(|attempts| attempts.parse().ok())
+            .unwrap_or(0);
+        Some((key, val, attempts))
+    }
+
+    /// Report a failed delivery of a key-value pair taken by `get_with_attempts`.
+    ///
+    /// The pair is put back with one more attempt, or moved to the dead-letter queue
+    /// when the attempts reach `max_attempts`. Returns true if the pair is dead-lettered.
+    pub fn nack(&self, key: &str, value: &str, attempts: u32) -> Result<bool, &'static str> {
+        let attempts = attempts.saturating_add(1);
+        if self.base.is_dead(attempts) {
+            let dlq = self.dead_letters()?;
+            let result = dlq.put(key, value);
+            dlq.close();
+            return result.map(|_| true);
+        }
+        self.put(key, value)?;
+        if replace_str_attribute(
+            self.base.k2h,
+            key,
+            ATTEMPTS_ATTR,
+            Some(&attempts.to_string()),
+        ) {
+            Ok(false)
+        } else {
+            Err("Failed to set attempts attribute")
+        }
+    }
+
+    /// Get the dead-letter queue of the KeyQueue, which has the password and the expire of the
+    /// KeyQueue.
+    pub fn dead_letters(&self) -> Result<KeyQueue, &'static str> {
+        KeyQueue::new(
+            self.base.k2h,
+            self.base.fifo,
+            Some(self.base.dead_letter_prefix()),
+            self.base.password.clone(),
+            self.base.expire_duration,
+        )
+    }
 }
 /// KeyQueueBuilder provides a builder pattern for creating KeyQueue instances.
 ///
@@ -2493,14 +4109,17 @@ impl KeyQueue {
 /// // check if KeyQueue is not null
 /// assert!(qb1.handle() != 0, "KeyQueue handle should not be zero");
 /// ```
+#[cfg(feature = "libk2hash")]
 pub struct KeyQueueBuilder {
     k2h: u64,
     fifo: bool,
     prefix: Option<String>,
     password: Option<String>,
     expire_duration: Option<u64>,
+    max_attempts: Option<u32>,
 }
 
+#[cfg(feature = "libk2hash")]
 impl KeyQueueBuilder {
     /// Create a new KeyQueueBuilder.
     pub fn new(k2h: u64) -> Self {
@@ -2510,6 +4129,7 @@ impl KeyQueueBuilder {
             prefix: None,
             password: None,
             expire_duration: None,
+            max_attempts: None,
         }
     }
 
@@ -2537,15 +4157,1150 @@ impl KeyQueueBuilder {
         self
     }
 
+    /// Set the max delivery attempts before a pair is moved to the dead-letter queue.
+    pub fn max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = Some(max_attempts);
+        self
+    }
+
     /// Create a new KeyQueue instance with options.
     pub fn build(self) -> Result<KeyQueue, &'static str> {
-        KeyQueue::new(
+        let mut q = KeyQueue::new(
             self.k2h,
             self.fifo,
             self.prefix,
             self.password,
             self.expire_duration,
-        )
+        )?;
+        q.base.max_attempts = self.max_attempts;
+        Ok(q)
+    }
+}
+
+/// Returns the current unix time in seconds.
+#[cfg(feature = "libk2hash")]
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+/// Returns the current unix time in milliseconds.
+#[cfg(feature = "libk2hash")]
+fn unix_now_millis() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis())
+}
+
+/// Returns a message id that is unique among processes sharing one k2hash file.
+#[cfg(feature = "libk2hash")]
+fn new_message_id() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos());
+    let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+    format!("{:x}-{:x}-{:x}", nanos, std::process::id(), count)
+}
+
+/// Reads a string value of a key directly from the k2hash handle.
+#[cfg(feature = "libk2hash")]
+fn read_str_value(k2h: u64, key: &str) -> Option<String> {
+    let k = CString::new(key).unwrap();
+    let ptr = unsafe { k2h_get_str_direct_value_wp(k2h, k.as_ptr(), ptr::null()) };
+    if ptr.is_null() {
+        return None;
+    }
+    let val = unsafe { CStr::from_ptr(ptr).to_string_lossy().into_owned() };
+    unsafe { libc::free(ptr as *mut c_void) };
+    Some(val)
+}
+
+/// Reads all attributes of a key as raw name and value bytes.
+#[cfg(feature = "libk2hash")]
+fn read_attributes(k2h: u64, key: &str) -> Vec<(Vec<u8>, Vec<u8>)> {
+    let k = CString::new(key).unwrap();
+    read_attributes_raw(k2h, k.as_bytes_with_nul())
+}
+
+/// Reads attributes of a key in binary.
+#[cfg(feature = "libk2hash")]
+fn read_attributes_raw(k2h: u64, key: &[u8]) -> Vec<(Vec<u8>, Vec<u8>)> {
+    let mut cnt: c_int = 0;
+    let pack = unsafe { k2h_get_direct_attrs(k2h, key.as_ptr(), key.len(), &mut cnt) };
+    let mut attrs = Vec::new();
+    if pack.is_null() {
+        return attrs;
+    }
+    for i in 0..cnt.max(0) as usize {
+        let attr_pack: &K2hAttrPack = unsafe { &*(pack as *const K2hAttrPack).add(i) };
+        let name = unsafe { std::slice::from_raw_parts(attr_pack.pkey, attr_pack.keylength) };
+        let val = unsafe { std::slice::from_raw_parts(attr_pack.pval, attr_pack.vallength) };
+        attrs.push((name.to_vec(), val.to_vec()));
+    }
+    unsafe { k2h_free_attrpack(pack, cnt) };
+    attrs
+}
+
+/// Reads a string attribute of a key which was set by `replace_str_attribute`.
+#[cfg(feature = "libk2hash")]
+fn read_str_attribute(k2h: u64, key: &str, name: &str) -> Option<String> {
+    let c_name = CString::new(name).unwrap();
+    read_attributes(k2h, key)
+        .into_iter()
+        .find(|(n, _)| n.as_slice() == c_name.as_bytes_with_nul())
+        .map(|(_, v)| {
+            let v = v.strip_suffix(&[0]).unwrap_or(&v);
+            String::from_utf8_lossy(v).into_owned()
+        })
+}
+
+/// Replaces a string attribute of a key, so the key keeps a single attribute of the name.
+///
+/// `None` removes the attribute.
+#[cfg(feature = "libk2hash")]
+fn replace_str_attribute(k2h: u64, key: &str, name: &str, value: Option<&str>) -> bool {
+    let k = CString::new(key).unwrap();
+    let k = k.as_bytes_with_nul();
+    let Some(val) = read_value_raw(k2h, k) else {
+        return false;
+    };
+    let n = CString::new(name).unwrap().into_bytes_with_nul();
+    let mut attrs = read_attributes_raw(k2h, k);
+    attrs.retain(|(a, _)| *a != n);
+    if let Some(value) = value {
+        attrs.push((n, CString::new(value).unwrap().into_bytes_with_nul()));
+    }
+    set_all_raw(k2h, k, &val, &read_subkeys_raw(k2h, k), &attrs)
+}
+
+/// The state of a lease written before its item is popped.
+#[cfg(feature = "libk2hash")]
+const LEASE_PENDING: &str = "pending";
+/// The state of a lease whose item is popped.
+#[cfg(feature = "libk2hash")]
+const LEASE_HELD: &str = "held";
+
+/// ReliableQueue provides at-least-once delivery using k2hash queues.
+///
+/// A popped item is moved into an in-flight list with a lease deadline. The item is
+/// deleted by `ack`, otherwise `recover_expired` puts it back after the lease expires.
+/// Every process that opens the same k2hash file with the same prefix shares the queue.
+///
+/// # Examples
+///
+/// ```
+/// use k2hash_rust::{K2hash, ReliableQueue};
+/// let db = K2hash::open_mem().expect("open_mem failed");
+/// let q = ReliableQueue::new(db.handle(), "jobs".to_string(), 30)
+///     .expect("ReliableQueue creation failed");
+/// let id = q.put("hello").expect("Push operation failed");
+/// if let Some((popped_id, value)) = q.get() {
+///     assert_eq!(popped_id, id, "Get operation returned unexpected id");
+///     assert_eq!(value, "hello", "Get operation returned unexpected value");
+///     assert!(q.ack(&popped_id).is_ok(), "Ack operation failed");
+/// } else {
+///     panic!("Get operation failed or returned None");
+/// }
+/// ```
+#[cfg(feature = "libk2hash")]
+pub struct ReliableQueue {
+    k2h: u64,
+    prefix: String,
+    visibility_timeout: u64,
+    ready: KeyQueue,
+    inflight: Queue,
+}
+
+#[cfg(feature = "libk2hash")]
+impl ReliableQueue {
+    /// Create a new ReliableQueue.
+    ///
+    /// `visibility_timeout` is the lease duration in seconds of a popped item.
+    pub fn new(k2h: u64, prefix: String, visibility_timeout: u64) -> Result<Self, &'static str> {
+        if prefix.is_empty() {
+            return Err("prefix should be passed");
+        }
+        let ready = KeyQueue::new(k2h, true, Some(format!("{}.ready.", prefix)), None, None)?;
+        let inflight = Queue::new(k2h, true, Some(format!("{}.inflight.", prefix)), None, None)?;
+        Ok(ReliableQueue {
+            k2h,
+            prefix,
+            visibility_timeout,
+            ready,
+            inflight,
+        })
+    }
+
+    fn message_key(&self, id: &str) -> String {
+        format!("{}.msg.{}", self.prefix, id)
+    }
+
+    fn lease_key(&self, id: &str) -> String {
+        format!("{}.lease.{}", self.prefix, id)
+    }
+
+    /// Put a value into the ReliableQueue and return its message id.
+    pub fn put(&self, value: &str) -> Result<String, &'static str> {
+        let id = new_message_id();
+        self.ready.put(&self.message_key(&id), value)?;
+        Ok(id)
+    }
+
+    /// Get a message id and its value from the ReliableQueue.
+    ///
+    /// The item stays in-flight until `ack` is called or its lease expires. The lease is written
+    /// as pending before the item is popped and marked held after it, so a crash in between
+    /// leaves the item leased and ready, and `recover_expired` releases the lease without
+    /// putting the item back twice. Each get owns its lease by a token, so a get which loses
+    /// the peeked item to another consumer drops only its own lease.
+    pub fn get(&self) -> Option<(String, String)> {
+        let (key, _) = self.ready.element(0)?;
+        let id = self.message_id(&key);
+        let token = new_message_id();
+        if !self.lease(&id, &token, LEASE_PENDING) {
+            return None;
+        }
+        let Some((popped_key, value)) = self.ready.get() else {
+            self.release(&id, &token);
+            return None;
+        };
+        let popped_id = self.message_id(&popped_key);
+        if popped_id != id {
+            // another consumer took the peeked item first, so lease the popped one instead.
+            self.release(&id, &token);
+            if !self.lease(&popped_id, &token, LEASE_HELD) {
+                // give the item back rather than losing it.
+                let _ = self.ready.put(&popped_key, &value);
+                return None;
+            }
+        } else {
+            // a pending lease is recovered too, so a failure here is not fatal.
+            self.write_lease(&id, &token, LEASE_HELD);
+        }
+        // the message key is kept until ack, which tells recover_expired the item is not done.
+        let c_key = CString::new(popped_key).unwrap();
+        let c_val = CString::new(value.as_str()).unwrap();
+        unsafe {
+            k2h_set_str_value_wa(
+                self.k2h,
+                c_key.as_ptr(),
+                c_val.as_ptr(),
+                ptr::null(),
+                ptr::null(),
+            )
+        };
+        Some((popped_id, value))
+    }
+
+    fn message_id(&self, key: &str) -> String {
+        key.strip_prefix(&format!("{}.msg.", self.prefix))
+            .unwrap_or(key)
+            .to_string()
+    }
+
+    /// Writes the lease of an item and its in-flight entry.
+    fn lease(&self, id: &str, token: &str, state: &str) -> bool {
+        if !self.write_lease(id, token, state) {
+            return false;
+        }
+        let deadline = unix_now() + self.visibility_timeout;
+        if self
+            .inflight
+            .put(&format!("{} {} {}", deadline, id, token))
+            .is_err()
+        {
+            self.release(id, token);
+            return false;
+        }
+        true
+    }
+
+    /// Writes the lease key of an item, which holds the token of its owner and its state.
+    fn write_lease(&self, id: &str, token: &str, state: &str) -> bool {
+        let lease_key = CString::new(self.lease_key(id)).unwrap();
+        let c_val = CString::new(format!("{} {}", token, state)).unwrap();
+        unsafe {
+            k2h_set_str_value_wa(
+                self.k2h,
+                lease_key.as_ptr(),
+                c_val.as_ptr(),
+                ptr::null(),
+                ptr::null(),
+            )
+        }
+    }
+
+    /// Reads the owner token and the state of the lease of an item.
+    fn read_lease(&self, id: &str) -> Option<(String, String)> {
+        let lease = read_str_value(self.k2h, &self.lease_key(id))?;
+        let (token, state) = lease.split_once(' ')?;
+        Some((token.to_string(), state.to_string()))
+    }
+
+    /// Removes the lease of an item if it is still owned by the token.
+    fn release(&self, id: &str, token: &str) {
+        if self.read_lease(id).is_some_and(|(owner, _)| owner == token) {
+            let lease_key = CString::new(self.lease_key(id)).unwrap();
+            unsafe { k2h_remove_str(self.k2h, lease_key.as_ptr()) };
+        }
+    }
+
+    /// Acknowledge a message and delete it from the ReliableQueue.
+    pub fn ack(&self, id: &str) -> Result<(), &'static str> {
+        let lease_key = self.lease_key(id);
+        if read_str_value(self.k2h, &lease_key).is_none() {
+            return Err("message is not in-flight");
+        }
+        let c_key = CString::new(lease_key).unwrap();
+        let result = unsafe { k2h_remove_str(self.k2h, c_key.as_ptr()) };
+        if !result {
+            return Err("k2h_remove_str returns error");
+        }
+        let c_key = CString::new(self.message_key(id)).unwrap();
+        unsafe { k2h_remove_str(self.k2h, c_key.as_ptr()) };
+        Ok(())
+    }
+
+    /// Put the in-flight items whose leases expired back to the ReliableQueue.
+    ///
+    /// An entry is popped only after its item is put back or the entry is copied to the tail,
+    /// so a crash in between leaves a stale entry rather than losing the item. Entries whose
+    /// lease was released or taken over by another get, and items acknowledged meanwhile, are
+    /// just dropped. Returns the number of items put back.
+    pub fn recover_expired(&self) -> Result<usize, &'static str> {
+        let now = unix_now();
+        let mut recovered = 0;
+        for _ in 0..self.inflight.qsize() {
+            let entry = match self.inflight.element(0) {
+                Some(entry) => entry,
+                None => break,
+            };
+            let mut fields = entry.splitn(3, ' ');
+            if let (Some(deadline), Some(id), Some(token)) =
+                (fields.next(), fields.next(), fields.next())
+            {
+                let deadline = deadline.parse::<u64>().unwrap_or(0);
+                match self.read_lease(id) {
+                    Some((owner, state)) if owner == token => {
+                        if deadline <= now {
+                            let message_key = self.message_key(id);
+                            // ack removes the message key, so a missing one is done.
+                            if let Some(value) = read_str_value(self.k2h, &message_key) {
+                                // a get stopped before popping leaves the item ready, so it is
+                                // not put back twice.
+                                if state != LEASE_PENDING || !self.is_ready(&message_key) {
+                                    self.ready.put(&message_key, &value)?;
+                                    recovered += 1;
+                                }
+                            }
+                            self.release(id, token);
+                        } else {
+                            self.inflight.put(&entry)?;
+                        }
+                    }
+                    // broken entries and released leases are just popped.
+                    _ => {}
+                }
+            }
+            match self.inflight.get() {
+                // another process popped the peeked entry first, so keep the popped one.
+                Some(popped) if popped != entry => self.inflight.put(&popped)?,
+                Some(_) => {}
+                None => break,
+            }
+        }
+        Ok(recovered)
+    }
+
+    /// Checks if a message key is waiting for delivery by scanning the ready queue.
+    fn is_ready(&self, message_key: &str) -> bool {
+        (0..self.ready.qsize()).any(|i| {
+            self.ready
+                .element(i)
+                .is_some_and(|(key, _)| key == message_key)
+        })
+    }
+
+    /// Get the number of items waiting for delivery.
+    pub fn qsize(&self) -> usize {
+        self.ready.qsize()
+    }
+
+    /// Get the number of in-flight entries, including acknowledged ones not yet swept by `recover_expired`.
+    pub fn inflight_size(&self) -> usize {
+        self.inflight.qsize()
+    }
+
+    /// Close the ReliableQueue.
+    pub fn close(&self) -> bool {
+        let ready = self.ready.close();
+        let inflight = self.inflight.close();
+        ready && inflight
+    }
+}
+
+/// PriorityQueue serves values of a higher priority first using one k2hash queue per priority level.
+///
+/// Priorities are `0..levels` and a larger number is a higher priority. When weights are set,
+/// levels are served by smooth weighted round-robin so that low priorities are not starved.
+///
+/// # Examples
+///
+/// ```
+/// use k2hash_rust::{K2hash, PriorityQueueBuilder};
+/// let db = K2hash::open_mem().expect("open_mem failed");
+/// let q = PriorityQueueBuilder::new(db.handle())
+///     .prefix("jobs".to_string())
+///     .levels(3)
+///     .build()
+///     .expect("PriorityQueue creation failed");
+/// assert!(q.put(0, "low").is_ok(), "Push operation failed");
+/// assert!(q.put(2, "high").is_ok(), "Push operation failed");
+/// assert_eq!(q.qsize(), 2, "PriorityQueue size should be 2");
+/// assert_eq!(q.get(), Some((2, "high".to_string())));
+/// assert_eq!(q.get(), Some((0, "low".to_string())));
+/// ```
+#[cfg(feature = "libk2hash")]
+pub struct PriorityQueue {
+    levels: Vec<Queue>,
+    weights: Option<Vec<u32>>,
+    credits: Mutex<Vec<i64>>,
+}
+
+#[cfg(feature = "libk2hash")]
+impl PriorityQueue {
+    /// Create a new PriorityQueue.
+    pub fn new(
+        k2h: u64,
+        levels: usize,
+        fifo: bool,
+        prefix: String,
+        password: Option<String>,
+        expire_duration: Option<u64>,
+        weights: Option<Vec<u32>>,
+    ) -> Result<Self, &'static str> {
+        if levels == 0 {
+            return Err("levels should be positive");
+        }
+        if prefix.is_empty() {
+            return Err("prefix should be passed");
+        }
+        if let Some(ref w) = weights {
+            if w.len() != levels {
+                return Err("weights should have an entry per level");
+            }
+            if w.iter().all(|weight| *weight == 0) {
+                return Err("weights should not be all zero");
+            }
+        }
+        let mut queues = Vec::with_capacity(levels);
+        for level in 0..levels {
+            queues.push(Queue::new(
+                k2h,
+                fifo,
+                Some(format!("{}.p{}.", prefix, level)),
+                password.clone(),
+                expire_duration,
+            )?);
+        }
+        Ok(PriorityQueue {
+            levels: queues,
+            weights,
+            credits: Mutex::new(vec![0; levels]),
+        })
+    }
+
+    /// Get the number of priority levels.
+    pub fn levels(&self) -> usize {
+        self.levels.len()
+    }
+
+    /// Put a value into the PriorityQueue with a priority.
+    pub fn put(&self, priority: usize, value: &str) -> Result<(), &'static str> {
+        match self.levels.get(priority) {
+            Some(q) => q.put(value),
+            None => Err("priority is out of range"),
+        }
+    }
+
+    /// Get a value and its priority from the PriorityQueue.
+    pub fn get(&self) -> Option<(usize, String)> {
+        if let Some(level) = self.next_weighted_level() {
+            if let Some(value) = self.levels[level].get() {
+                return Some((level, value));
+            }
+        }
+        // strict priority order, also used when a weighted pick lost a race with other consumers.
+        for (level, q) in self.levels.iter().enumerate().rev() {
+            if let Some(value) = q.get() {
+                return Some((level, value));
+            }
+        }
+        None
+    }
+
+    /// Picks a non-empty level by smooth weighted round-robin.
+    fn next_weighted_level(&self) -> Option<usize> {
+        let weights = self.weights.as_ref()?;
+        let mut credits = self.credits.lock().unwrap();
+        let mut total: i64 = 0;
+        let mut best: Option<usize> = None;
+        for (level, q) in self.levels.iter().enumerate() {
+            if weights[level] == 0 || q.empty() {
+                continue;
+            }
+            credits[level] += weights[level] as i64;
+            total += weights[level] as i64;
+            match best {
+                Some(b) if credits[b] > credits[level] => {}
+                _ => best = Some(level),
+            }
+        }
+        let best = best?;
+        credits[best] -= total;
+        Some(best)
+    }
+
+    /// Get the total size of the PriorityQueue.
+    pub fn qsize(&self) -> usize {
+        self.levels.iter().map(|q| q.qsize()).sum()
+    }
+
+    /// Get the size of a priority level, or `None` if the priority is out of range.
+    pub fn level_qsize(&self, priority: usize) -> Option<usize> {
+        self.levels.get(priority).map(|q| q.qsize())
+    }
+
+    /// Check if the PriorityQueue is empty.
+    pub fn empty(&self) -> bool {
+        self.levels.iter().all(|q| q.empty())
+    }
+
+    /// Remove all objects from the PriorityQueue.
+    pub fn clear(&self) -> bool {
+        let mut result = true;
+        for q in self.levels.iter() {
+            result &= q.clear();
+        }
+        result
+    }
+
+    /// Close the PriorityQueue.
+    pub fn close(&self) -> bool {
+        let mut result = true;
+        for q in self.levels.iter() {
+            result &= q.close();
+        }
+        result
+    }
+}
+
+/// PriorityQueueBuilder provides a builder pattern for creating PriorityQueue instances.
+///
+/// # Examples
+///
+/// ```
+/// use k2hash_rust::{K2hash, PriorityQueueBuilder};
+/// let db = K2hash::open_mem().expect("open_mem failed");
+/// let q = PriorityQueueBuilder::new(db.handle())
+///     .prefix("jobs".to_string())
+///     .levels(2)
+///     .weights(vec![1, 3]) // serve the high priority 3 times as often as the low one
+///     .build()
+///     .expect("PriorityQueue creation failed");
+/// assert_eq!(q.levels(), 2, "PriorityQueue should have 2 levels");
+/// ```
+#[cfg(feature = "libk2hash")]
+pub struct PriorityQueueBuilder {
+    k2h: u64,
+    levels: usize,
+    fifo: bool,
+    prefix: String,
+    password: Option<String>,
+    expire_duration: Option<u64>,
+    weights: Option<Vec<u32>>,
+}
+
+#[cfg(feature = "libk2hash")]
+impl PriorityQueueBuilder {
+    /// Create a new PriorityQueueBuilder instance.
+    pub fn new(k2h: u64) -> Self {
+        PriorityQueueBuilder {
+            k2h,
+            levels: 3,
+            fifo: true,
+            prefix: "priority".to_string(),
+            password: None,
+            expire_duration: None,
+            weights: None,
+        }
+    }
+
+    /// Set the number of priority levels.
+    pub fn levels(mut self, levels: usize) -> Self {
+        self.levels = levels;
+        self
+    }
+    /// Set the FIFO flag for each level.
+    pub fn fifo(mut self, fifo: bool) -> Self {
+        self.fifo = fifo;
+        self
+    }
+    /// Set the prefix of the PriorityQueue.
+    pub fn prefix(mut self, prefix: String) -> Self {
+        self.prefix = prefix;
+        self
+    }
+    /// Set the password of the PriorityQueue.
+    pub fn password(mut self, password: String) -> Self {
+        self.password = Some(password);
+        self
+    }
+    /// Set the expiration duration of the PriorityQueue.
+    pub fn expire_duration(mut self, expire_duration: u64) -> Self {
+        self.expire_duration = Some(expire_duration);
+        self
+    }
+    /// Set the weights of each level for weighted-fair scheduling.
+    pub fn weights(mut self, weights: Vec<u32>) -> Self {
+        self.weights = Some(weights);
+        self
+    }
+    /// Build the PriorityQueue.
+    pub fn build(self) -> Result<PriorityQueue, &'static str> {
+        PriorityQueue::new(
+            self.k2h,
+            self.levels,
+            self.fifo,
+            self.prefix,
+            self.password,
+            self.expire_duration,
+            self.weights,
+        )
+    }
+}
+
+/// The due times and the keys of the messages of a DelayedQueue in due order.
+#[cfg(feature = "libk2hash")]
+type DueKeys = Vec<(u128, String)>;
+
+/// DelayedQueue provides messages which become visible only after a delay.
+///
+/// Each message is stored as a subkey of the `{prefix}.delayed` key. The subkey is named by the
+/// due time (unix time in milliseconds, zero-padded) of the message, so `get` takes the earliest
+/// due message without moving the others. The DelayedQueue is not built on a KeyQueue, because a
+/// KeyQueue is ordered by the put and a message put with a short delay behind one with a long
+/// delay could only be taken by popping and pushing back every message ahead of it.
+///
+/// The DelayedQueue caches the subkeys sorted by the due time. Every put and get writes a new
+/// stamp to the `{prefix}.delayed.stamp` key, and the cache is read again only when the stamp
+/// differs from the one of the cache, so a DelayedQueue on another handle or process is seen
+/// by the next call. A message which another consumer took in the meantime is skipped by `get`.
+///
+/// # Examples
+///
+/// ```
+/// use k2hash_rust::{DelayedQueue, K2hash};
+/// use std::time::Duration;
+/// let db = K2hash::open_mem().expect("open_mem failed");
+/// let q = DelayedQueue::new(db.handle(), "retry".to_string()).expect("DelayedQueue creation failed");
+/// assert!(q.put_delayed("later", Duration::from_secs(60)).is_ok(), "Push operation failed");
+/// assert!(q.put("now").is_ok(), "Push operation failed");
+/// assert_eq!(q.get(), Some("now".to_string()));
+/// assert_eq!(q.get(), None, "The delayed value should not be visible yet");
+/// assert!(q.next_due().is_some(), "The delayed value should have a due time");
+/// ```
+#[cfg(feature = "libk2hash")]
+pub struct DelayedQueue {
+    k2h: u64,
+    prefix: String,
+    index: CString,
+    stamp: CString,
+    due_keys: Mutex<Option<(String, DueKeys)>>,
+}
+
+#[cfg(feature = "libk2hash")]
+impl DelayedQueue {
+    /// Create a new DelayedQueue.
+    pub fn new(k2h: u64, prefix: String) -> Result<Self, &'static str> {
+        if prefix.is_empty() {
+            return Err("prefix should be passed");
+        }
+        let index = CString::new(format!("{}.delayed", prefix)).unwrap();
+        let stamp = CString::new(format!("{}.delayed.stamp", prefix)).unwrap();
+        // the subkeys need a parent key.
+        if read_str_value(k2h, &format!("{}.delayed", prefix)).is_none() {
+            let empty = CString::new("").unwrap();
+            let result = unsafe {
+                k2h_set_str_value_wa(
+                    k2h,
+                    index.as_ptr(),
+                    empty.as_ptr(),
+                    ptr::null(),
+                    ptr::null(),
+                )
+            };
+            if !result {
+                return Err("Failed to create the delayed key");
+            }
+        }
+        Ok(DelayedQueue {
+            k2h,
+            prefix,
+            index,
+            stamp,
+            due_keys: Mutex::new(None),
+        })
+    }
+
+    /// Reads the stamp which is written by every change of the subkeys.
+    fn read_stamp(&self) -> String {
+        read_str_value(self.k2h, &self.stamp.to_string_lossy()).unwrap_or_default()
+    }
+
+    /// Writes a new stamp after a change of the subkeys and applies the change to the cache.
+    ///
+    /// The cache is dropped if the stamp was changed by another DelayedQueue since it was read.
+    fn update_due_keys<F: FnOnce(&mut DueKeys)>(&self, f: F) {
+        let current = self.read_stamp();
+        let stamp = new_message_id();
+        let c_stamp = CString::new(stamp.as_str()).unwrap();
+        let written = unsafe {
+            k2h_set_str_value_wa(
+                self.k2h,
+                self.stamp.as_ptr(),
+                c_stamp.as_ptr(),
+                ptr::null(),
+                ptr::null(),
+            )
+        };
+        let mut cache = self.due_keys.lock().unwrap();
+        match cache.as_mut() {
+            Some((cached, keys)) if written && *cached == current => {
+                f(keys);
+                *cached = stamp;
+            }
+            _ => *cache = None,
+        }
+    }
+
+    /// Returns the due times and the keys of the messages in due order.
+    fn due_keys(&self) -> DueKeys {
+        let stamp = self.read_stamp();
+        let mut cache = self.due_keys.lock().unwrap();
+        if let Some((cached, keys)) = cache.as_ref() {
+            if *cached == stamp {
+                return keys.clone();
+            }
+        }
+        let due_prefix = format!("{}.due.", self.prefix);
+        let mut keys: DueKeys = read_subkeys_raw(self.k2h, self.index.as_bytes_with_nul())
+            .into_iter()
+            .filter_map(|subkey| {
+                let subkey = subkey.strip_suffix(&[0]).unwrap_or(&subkey);
+                let key = String::from_utf8_lossy(subkey).into_owned();
+                let (due, _) = key.strip_prefix(&due_prefix)?.split_once('.')?;
+                Some((due.parse().ok()?, key))
+            })
+            .collect();
+        keys.sort();
+        *cache = Some((stamp, keys.clone()));
+        keys
+    }
+
+    /// Put a value into the DelayedQueue which is visible immediately.
+    pub fn put(&self, value: &str) -> Result<(), &'static str> {
+        self.put_delayed(value, Duration::ZERO)
+    }
+
+    /// Put a value into the DelayedQueue which becomes visible after the delay.
+    pub fn put_delayed(&self, value: &str, delay: Duration) -> Result<(), &'static str> {
+        self.put_at(value, SystemTime::now() + delay)
+    }
+
+    /// Put a value into the DelayedQueue which becomes visible at the due time.
+    ///
+    /// A due time in the past makes the value visible immediately.
+    pub fn put_at(&self, value: &str, due: SystemTime) -> Result<(), &'static str> {
+        let due = due.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis());
+        let key = CString::new(format!(
+            "{}.due.{:020}.{}",
+            self.prefix,
+            due,
+            new_message_id()
+        ))
+        .unwrap();
+        let val = CString::new(value).unwrap();
+        let result = unsafe {
+            k2h_add_subkey_wa(
+                self.k2h,
+                self.index.as_bytes_with_nul().as_ptr(),
+                self.index.as_bytes_with_nul().len(),
+                key.as_bytes_with_nul().as_ptr(),
+                key.as_bytes_with_nul().len(),
+                val.as_bytes_with_nul().as_ptr(),
+                val.as_bytes_with_nul().len(),
+                ptr::null(),
+                ptr::null(),
+            )
+        };
+        if !result {
+            return Err("Failed to set value");
+        }
+        let key = (due, key.into_string().unwrap());
+        self.update_due_keys(|keys| {
+            let at = keys.partition_point(|k| *k < key);
+            keys.insert(at, key);
+        });
+        Ok(())
+    }
+
+    /// Get a value whose due time has come from the DelayedQueue.
+    ///
+    /// Values which are not due yet are kept in the queue.
+    pub fn get(&self) -> Option<String> {
+        let now = unix_now_millis();
+        for (due, key) in self.due_keys() {
+            if due > now {
+                break;
+            }
+            let value = read_str_value(self.k2h, &key);
+            let c_key = CString::new(key).unwrap();
+            // another consumer took the value if it is not a subkey any more.
+            let removed =
+                unsafe { k2h_remove_str_subkey(self.k2h, self.index.as_ptr(), c_key.as_ptr()) };
+            unsafe { k2h_remove_str(self.k2h, c_key.as_ptr()) };
+            let key = c_key.into_string().unwrap();
+            self.update_due_keys(|keys| keys.retain(|(_, k)| *k != key));
+            if let (true, Some(value)) = (removed, value) {
+                return Some(value);
+            }
+        }
+        None
+    }
+
+    /// Get the earliest due time in the DelayedQueue.
+    pub fn next_due(&self) -> Option<SystemTime> {
+        self.due_keys()
+            .first()
+            .map(|(due, _)| UNIX_EPOCH + Duration::from_millis(*due as u64))
+    }
+
+    /// Get the size of the DelayedQueue, including values which are not due yet.
+    pub fn qsize(&self) -> usize {
+        self.due_keys().len()
+    }
+
+    /// Check if the DelayedQueue is empty.
+    pub fn empty(&self) -> bool {
+        self.qsize() == 0
+    }
+}
+
+/// Refuses to open a database with a k2hash C-library older than `MIN_LIBRARY_VERSION`.
+///
+/// A library whose version output cannot be parsed is not refused, because a banner in an
+/// unknown format does not tell that the library is old. A warning is written once instead.
+#[cfg(feature = "libk2hash")]
+fn check_library_version() -> Result<(), &'static str> {
+    match K2hash::library_version() {
+        Ok(version) if version < MIN_LIBRARY_VERSION => Err("unsupported k2hash C-library version"),
+        Ok(_) => Ok(()),
+        Err(e) => {
+            static WARNED: Once = Once::new();
+            WARNED.call_once(|| {
+                #[cfg(feature = "tracing")]
+                tracing::warn!(target: "k2hash", "{}, assuming a supported version", e);
+                #[cfg(not(feature = "tracing"))]
+                eprintln!("k2hash_rust: {}, assuming a supported version", e);
+            });
+            Ok(())
+        }
+    }
+}
+
+/// Runs a C-API which prints to a `FILE*` on a memory stream and copies the output to the writer.
+#[cfg(feature = "libk2hash")]
+fn write_stream_to<W: Write, F: FnOnce(*mut c_void) -> bool>(
+    writer: &mut W,
+    f: F,
+) -> Result<bool, &'static str> {
+    let mut buf: *mut c_char = ptr::null_mut();
+    let mut size: libc::size_t = 0;
+    let file = unsafe { libc::open_memstream(&mut buf, &mut size) };
+    if file.is_null() {
+        return Err("Failed to open a memory stream");
+    }
+    let result = f(file as *mut c_void);
+    // closing the stream flushes it and updates the buffer and the size.
+    unsafe { libc::fclose(file) };
+    let output = if buf.is_null() {
+        Vec::new()
+    } else {
+        let output = unsafe { std::slice::from_raw_parts(buf as *const u8, size).to_vec() };
+        unsafe { libc::free(buf as *mut c_void) };
+        output
+    };
+    writer
+        .write_all(&output)
+        .map_err(|_| "Failed to write the output")?;
+    Ok(result)
+}
+
+/// RawKeys iterates all keys of a k2hash handle in binary.
+#[cfg(feature = "libk2hash")]
+struct RawKeys {
+    find: u64,
+}
+
+#[cfg(feature = "libk2hash")]
+impl RawKeys {
+    fn new(k2h: u64) -> Self {
+        RawKeys {
+            find: unsafe { k2h_find_first(k2h) },
+        }
+    }
+}
+
+#[cfg(feature = "libk2hash")]
+impl Iterator for RawKeys {
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.find == 0 {
+            return None;
+        }
+        let mut pkey: *mut u8 = ptr::null_mut();
+        let mut length: usize = 0;
+        if !unsafe { k2h_find_get_key(self.find, &mut pkey, &mut length) } || pkey.is_null() {
+            self.find = 0;
+            return None;
+        }
+        let key = unsafe { std::slice::from_raw_parts(pkey, length) }.to_vec();
+        unsafe { libc::free(pkey as *mut c_void) };
+        self.find = unsafe { k2h_find_next(self.find) };
+        Some(key)
+    }
+}
+
+/// Reads a value of a key in binary.
+#[cfg(feature = "libk2hash")]
+fn read_value_raw(k2h: u64, key: &[u8]) -> Option<Vec<u8>> {
+    let mut length: usize = 0;
+    let ptr = unsafe { k2h_get_direct_value(k2h, key.as_ptr(), key.len(), &mut length) };
+    if ptr.is_null() {
+        return None;
+    }
+    let val = unsafe { std::slice::from_raw_parts(ptr, length).to_vec() };
+    unsafe { libc::free(ptr as *mut c_void) };
+    Some(val)
+}
+
+/// Reads subkeys of a key in binary.
+#[cfg(feature = "libk2hash")]
+fn read_subkeys_raw(k2h: u64, key: &[u8]) -> Vec<Vec<u8>> {
+    let mut cnt: c_int = 0;
+    let pack = unsafe { k2h_get_direct_subkeys(k2h, key.as_ptr(), key.len(), &mut cnt) };
+    let mut subkeys = Vec::new();
+    if pack.is_null() {
+        return subkeys;
+    }
+    for i in 0..cnt.max(0) as usize {
+        let key_pack: &K2hKeyPack = unsafe { &*(pack as *const K2hKeyPack).add(i) };
+        subkeys
+            .push(unsafe { std::slice::from_raw_parts(key_pack.pkey, key_pack.length) }.to_vec());
+    }
+    unsafe { k2h_free_keypack(pack, cnt) };
+    subkeys
+}
+
+/// Builds a key pack which borrows the keys.
+#[cfg(feature = "libk2hash")]
+fn key_packs(keys: &[Vec<u8>]) -> Vec<K2hKeyPack> {
+    keys.iter()
+        .map(|k| K2hKeyPack {
+            pkey: k.as_ptr() as *mut u8,
+            length: k.len(),
+        })
+        .collect()
+}
+
+/// Sets a value, subkeys and attributes of a key in binary.
+#[cfg(feature = "libk2hash")]
+fn set_all_raw(
+    k2h: u64,
+    key: &[u8],
+    value: &[u8],
+    subkeys: &[Vec<u8>],
+    attrs: &[(Vec<u8>, Vec<u8>)],
+) -> bool {
+    let key_packs = key_packs(subkeys);
+    let attr_packs: Vec<K2hAttrPack> = attrs
+        .iter()
+        .map(|(k, v)| K2hAttrPack {
+            pkey: k.as_ptr() as *mut u8,
+            keylength: k.len(),
+            pval: v.as_ptr() as *mut u8,
+            vallength: v.len(),
+        })
+        .collect();
+    unsafe {
+        k2h_set_all(
+            k2h,
+            key.as_ptr(),
+            key.len(),
+            value.as_ptr(),
+            value.len(),
+            pack_ptr(&key_packs),
+            key_packs.len() as c_int,
+            pack_ptr(&attr_packs),
+            attr_packs.len() as c_int,
+        )
+    }
+}
+
+/// Returns the pointer of a key or attribute pack, which is null when the pack is empty.
+#[cfg(feature = "libk2hash")]
+fn pack_ptr<T>(packs: &[T]) -> *const c_void {
+    if packs.is_empty() {
+        ptr::null()
+    } else {
+        packs.as_ptr() as *const c_void
+    }
+}
+
+/// TxKey is the value, subkeys and attributes of a key seen by `TxState`.
+#[cfg(feature = "libk2hash")]
+#[derive(Debug, Clone, Default, PartialEq)]
+struct TxKey {
+    value: Vec<u8>,
+    subkeys: Vec<Vec<u8>>,
+    attributes: Vec<(Vec<u8>, Vec<u8>)>,
+}
+
+/// TxState applies transaction records in order to a k2hash handle.
+///
+/// In dry-run mode the records are applied to a simulation of the changed keys on top of the
+/// database, so a record sees the changes of the earlier ones.
+#[cfg(feature = "libk2hash")]
+struct TxState {
+    k2h: u64,
+    dry_run: bool,
+    simulated: HashMap<Vec<u8>, Option<TxKey>>,
+}
+
+#[cfg(feature = "libk2hash")]
+impl TxState {
+    fn new(k2h: u64, dry_run: bool) -> Self {
+        TxState {
+            k2h,
+            dry_run,
+            simulated: HashMap::new(),
+        }
+    }
+
+    /// Reads a key from the simulation, or from the database.
+    fn load(&self, key: &[u8]) -> Option<TxKey> {
+        if let Some(simulated) = self.simulated.get(key) {
+            return simulated.clone();
+        }
+        Some(TxKey {
+            value: read_value_raw(self.k2h, key)?,
+            subkeys: read_subkeys_raw(self.k2h, key),
+            attributes: read_attributes_raw(self.k2h, key),
+        })
+    }
+
+    /// Applies a record, returning false if it does not change the key.
+    fn apply(&mut self, record: &TxRecord) -> Result<bool, &'static str> {
+        let key = record.key.as_slice();
+        let value = record.value.as_deref().unwrap_or_default();
+        let current = self.load(key);
+        let base = current.clone().unwrap_or_default();
+        let next = match record.operation {
+            TxOperation::SetAll => Some(TxKey {
+                value: value.to_vec(),
+                subkeys: record.subkeys.clone(),
+                attributes: record.attributes.clone(),
+            }),
+            TxOperation::ReplaceValue => Some(TxKey {
+                value: value.to_vec(),
+                ..base
+            }),
+            TxOperation::ReplaceSubkeys => Some(TxKey {
+                subkeys: record.subkeys.clone(),
+                ..base
+            }),
+            TxOperation::ReplaceAttributes => Some(TxKey {
+                attributes: record.attributes.clone(),
+                ..base
+            }),
+            TxOperation::OverwriteValue => Some(TxKey {
+                value: overwrite_value(&base.value, record)?,
+                ..base
+            }),
+            // a missing key can not be removed or renamed.
+            TxOperation::Remove | TxOperation::Rename if current.is_none() => return Ok(false),
+            TxOperation::Remove | TxOperation::Rename => None,
+        };
+        if next == current {
+            return Ok(false);
+        }
+        if self.dry_run {
+            if record.operation == TxOperation::Rename {
+                self.simulated.insert(record.exdata.clone(), current);
+            }
+            self.simulated.insert(key.to_vec(), next);
+            return Ok(true);
+        }
+        if write_tx_key(self.k2h, record, next.as_ref()) {
+            Ok(true)
+        } else {
+            Err("Failed to apply a transaction record")
+        }
+    }
+}
+
+/// Writes the state of a key after a transaction record to the database.
+#[cfg(feature = "libk2hash")]
+fn write_tx_key(k2h: u64, record: &TxRecord, next: Option<&TxKey>) -> bool {
+    let key = record.key.as_slice();
+    match (record.operation, next) {
+        (TxOperation::Remove, _) => unsafe { k2h_remove(k2h, key.as_ptr(), key.len()) },
+        (TxOperation::Rename, _) => unsafe {
+            k2h_rename(
+                k2h,
+                key.as_ptr(),
+                key.len(),
+                record.exdata.as_ptr(),
+                record.exdata.len(),
+            )
+        },
+        (TxOperation::ReplaceValue | TxOperation::OverwriteValue, Some(next)) => unsafe {
+            k2h_set_value(
+                k2h,
+                key.as_ptr(),
+                key.len(),
+                next.value.as_ptr(),
+                next.value.len(),
+            )
+        },
+        (TxOperation::ReplaceSubkeys, Some(next)) => {
+            let packs = key_packs(&next.subkeys);
+            unsafe {
+                k2h_set_subkeys(
+                    k2h,
+                    key.as_ptr(),
+                    key.len(),
+                    pack_ptr(&packs),
+                    packs.len() as c_int,
+                )
+            }
+        }
+        (_, Some(next)) => set_all_raw(k2h, key, &next.value, &next.subkeys, &next.attributes),
+        (_, None) => false,
     }
 }
 
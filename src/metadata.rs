@@ -0,0 +1,158 @@
+//
+// k2hash_rust
+//
+// Copyright 2025 LY Corporation.
+//
+// Rust driver for k2hash that is a NoSQL Key Value Store(KVS) library.
+// For k2hash, see https://github.com/yahoojapan/k2hash for the details.
+//
+// For the full copyright and license information, please view
+// the license file that was distributed with this source code.
+//
+// AUTHOR:   Hirotaka Wakabayashi
+// CREATE:   Fri, 17 Jul 2025
+// REVISION:
+//
+
+//! Typed access to the builtin attributes of a key.
+
+use std::collections::HashMap;
+use std::mem;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// The names are the ones of the builtin attributes of `K2hAttrBuiltin` in libk2hash
+// lib/k2hattrbuiltin.cc, which the C-library writes without the trailing NUL.
+
+/// The builtin attribute which holds the modification time of a key.
+pub(crate) const MTIME_ATTR: &[u8] = b"mtime";
+/// The builtin attribute which holds the expiration time of a key.
+pub(crate) const EXPIRE_ATTR: &[u8] = b"expire";
+/// The builtin attribute which holds the keys of the previous values of a key.
+pub(crate) const HISTORY_ATTR: &[u8] = b"history";
+/// The builtin attribute which holds the unique id of a value.
+pub(crate) const UNIQID_ATTR: &[u8] = b"uniqid";
+/// The builtin attribute which holds the salt of an encrypted value.
+pub(crate) const SALT_ATTR: &[u8] = b"salt";
+/// The builtin attribute which holds the iteration count of an encrypted value.
+pub(crate) const ITER_ATTR: &[u8] = b"iter";
+
+#[cfg(feature = "libk2hash")]
+const BUILTIN_ATTRS: [&[u8]; 6] = [
+    MTIME_ATTR,
+    EXPIRE_ATTR,
+    HISTORY_ATTR,
+    UNIQID_ATTR,
+    SALT_ATTR,
+    ITER_ATTR,
+];
+
+/// Returns the attribute name without its trailing NUL.
+pub(crate) fn attribute_name(name: &[u8]) -> &[u8] {
+    name.strip_suffix(b"\0").unwrap_or(name)
+}
+
+/// Check if an attribute name is one of the builtin attributes.
+#[cfg(feature = "libk2hash")]
+pub(crate) fn is_builtin_attribute(name: &[u8]) -> bool {
+    BUILTIN_ATTRS.contains(&attribute_name(name))
+}
+
+/// Check if the attributes of a key tell that its value is encrypted.
+#[cfg(feature = "libk2hash")]
+pub(crate) fn is_encrypted(attrs: &[(Vec<u8>, Vec<u8>)]) -> bool {
+    attrs
+        .iter()
+        .any(|(name, _)| matches!(attribute_name(name), SALT_ATTR | ITER_ATTR))
+}
+
+/// Decodes a native signed integer of `size` bytes at the head of the value.
+fn decode_int(val: &[u8], size: usize) -> Option<i64> {
+    let bytes = val.get(0..size)?;
+    match size {
+        4 => Some(i32::from_ne_bytes(bytes.try_into().ok()?).into()),
+        8 => Some(i64::from_ne_bytes(bytes.try_into().ok()?)),
+        _ => None,
+    }
+}
+
+/// Decodes a `struct timespec` in the native byte order.
+///
+/// The fields are sized by `time_t` and `long` of the platform, which lays `tv_nsec` right
+/// after `tv_sec` on the platforms libk2hash supports.
+pub(crate) fn decode_timespec(val: &[u8]) -> Option<SystemTime> {
+    let sec_size = mem::size_of::<libc::time_t>();
+    let sec = decode_int(val, sec_size)?;
+    let nsec = decode_int(val.get(sec_size..)?, mem::size_of::<libc::c_long>())?;
+    Some(UNIX_EPOCH + Duration::new(u64::try_from(sec).ok()?, u32::try_from(nsec).ok()?))
+}
+
+/// Decodes a `time_t` in the native byte order.
+pub(crate) fn decode_time(val: &[u8]) -> Option<SystemTime> {
+    let sec = decode_int(val, mem::size_of::<libc::time_t>())?;
+    Some(UNIX_EPOCH + Duration::from_secs(u64::try_from(sec).ok()?))
+}
+
+/// Decodes the NUL separated keys of the history attribute.
+pub(crate) fn decode_history(val: &[u8]) -> Vec<Vec<u8>> {
+    val.split(|b| *b == 0)
+        .filter(|key| !key.is_empty())
+        .map(|key| key.to_vec())
+        .collect()
+}
+
+/// KeyMetadata holds the builtin attributes of a key in typed fields.
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(feature = "libk2hash")] {
+/// use k2hash_rust::{K2hash, KeyMetadata};
+/// let db = K2hash::open_mem().expect("open_mem failed");
+/// assert!(db.set("hello", "world").is_ok(), "Set operation failed");
+/// assert!(db.set_attribute("hello", "owner", "alice").is_ok(), "Set attribute operation failed");
+/// let metadata = db.get_metadata("hello").expect("Get metadata operation failed");
+/// assert!(!metadata.encrypted, "The value should not be encrypted");
+/// assert_eq!(metadata.custom.get(b"owner\0".as_slice()), Some(&b"alice\0".to_vec()));
+/// # }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeyMetadata {
+    /// The modification time, recorded when `K2hash::enable_mtime` is enabled.
+    pub mtime: Option<SystemTime>,
+    /// The expiration time.
+    pub expire: Option<SystemTime>,
+    /// The keys which hold the previous values, recorded when `K2hash::enable_history` is enabled.
+    pub history_keys: Vec<Vec<u8>>,
+    /// Whether the value is encrypted.
+    pub encrypted: bool,
+    /// The attributes which are not builtin, such as the ones added by `K2hash::set_attribute`.
+    pub custom: HashMap<Vec<u8>, Vec<u8>>,
+}
+
+impl From<HashMap<Vec<u8>, Vec<u8>>> for KeyMetadata {
+    fn from(attrs: HashMap<Vec<u8>, Vec<u8>>) -> Self {
+        let mut metadata = KeyMetadata::default();
+        for (name, val) in attrs {
+            match attribute_name(&name) {
+                MTIME_ATTR => metadata.mtime = decode_timespec(&val),
+                EXPIRE_ATTR => metadata.expire = decode_time(&val),
+                HISTORY_ATTR => metadata.history_keys = decode_history(&val),
+                SALT_ATTR | ITER_ATTR => metadata.encrypted = true,
+                UNIQID_ATTR => {}
+                _ => {
+                    metadata.custom.insert(name, val);
+                }
+            }
+        }
+        metadata
+    }
+}
+
+//
+// Local variables:
+// tab-width: 4
+// c-basic-offset: 4
+// End:
+// vim600: expandtab sw=4 ts=4 fdm=marker
+// vim<600: expandtab sw=4 ts=4
+//
@@ -0,0 +1,627 @@
+//
+// k2hash_rust
+//
+// Copyright 2025 LY Corporation.
+//
+// Rust driver for k2hash that is a NoSQL Key Value Store(KVS) library.
+// For k2hash, see https://github.com/yahoojapan/k2hash for the details.
+//
+// For the full copyright and license information, please view
+// the license file that was distributed with this source code.
+//
+// AUTHOR:   Hirotaka Wakabayashi
+// CREATE:   Fri, 17 Jul 2025
+// REVISION:
+//
+
+//! Reader of k2hash transaction files written by `K2hash::begin_tx`.
+//!
+//! A transaction file is a sequence of records in the native byte order of the writer.
+//!
+//! ```text
+//! record  := head command
+//! head    := length(size_t) tv_sec(time_t) tv_nsec(long)      // length of command
+//! command := type(long)
+//!            key_length val_length skey_length attrs_length exdata_length (size_t)
+//!            key_pos val_pos skey_pos attrs_pos exdata_pos (off_t, from the command top)
+//!            data...
+//! subkeys := count(size_t) { length(size_t) subkey }*
+//! attrs   := count(size_t) { key_length(size_t) val_length(size_t) key val }*
+//! ```
+
+use std::fs::File;
+use std::io::{BufReader, ErrorKind, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The size of a record head, which is `K2HTRANSHEAD` of libk2hash lib/k2htrans.h:
+/// `size_t length` and `struct timespec ts`.
+const HEAD_SIZE: usize = 24;
+/// The size of a command without its data, which is `K2HCOMMAND` of libk2hash lib/k2hcommand.h:
+/// `long type`, five `size_t` lengths and five `off_t` positions before `byData`.
+const COMMAND_SIZE: usize = 88;
+
+// the layouts above are the ones of an LP64 writer, which this reader parses on any platform.
+// the native sizes match them on 64-bit platforms only.
+#[cfg(target_pointer_width = "64")]
+const _: () = assert!(
+    HEAD_SIZE
+        == std::mem::size_of::<usize>()
+            + std::mem::size_of::<libc::time_t>()
+            + std::mem::size_of::<libc::c_long>()
+);
+#[cfg(target_pointer_width = "64")]
+const _: () = assert!(
+    COMMAND_SIZE
+        == std::mem::size_of::<libc::c_long>()
+            + 5 * std::mem::size_of::<usize>()
+            + 5 * std::mem::size_of::<libc::off_t>()
+);
+/// The max size of a command, which protects from reading a broken length.
+const MAX_COMMAND_SIZE: u64 = 1 << 32;
+
+/// Attribute names and values in raw bytes.
+pub(crate) type RawAttributes = Vec<(Vec<u8>, Vec<u8>)>;
+
+/// TxOperation represents the operation type of a transaction record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TxOperation {
+    /// Set a value, subkeys and attributes of a key.
+    SetAll,
+    /// Replace the value of a key.
+    ReplaceValue,
+    /// Replace the subkeys of a key.
+    ReplaceSubkeys,
+    /// Replace the attributes of a key.
+    ReplaceAttributes,
+    /// Remove a key.
+    Remove,
+    /// Overwrite a part of the value of a key.
+    OverwriteValue,
+    /// Rename a key.
+    Rename,
+}
+
+impl TxOperation {
+    fn from_type(t: i64) -> Option<Self> {
+        match t {
+            1 => Some(TxOperation::SetAll),
+            2 => Some(TxOperation::ReplaceValue),
+            3 => Some(TxOperation::ReplaceSubkeys),
+            4 => Some(TxOperation::ReplaceAttributes),
+            5 => Some(TxOperation::Remove),
+            6 => Some(TxOperation::OverwriteValue),
+            7 => Some(TxOperation::Rename),
+            _ => None,
+        }
+    }
+}
+
+/// TxRecord represents a record in a k2hash transaction file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TxRecord {
+    /// The byte offset of the record in the file.
+    pub offset: u64,
+    /// The byte offset just after the record, where the next record starts.
+    pub end_offset: u64,
+    /// The time of the operation.
+    pub timestamp: SystemTime,
+    /// The operation type.
+    pub operation: TxOperation,
+    /// The key.
+    pub key: Vec<u8>,
+    /// The value, if the record has it.
+    pub value: Option<Vec<u8>>,
+    /// The subkeys.
+    pub subkeys: Vec<Vec<u8>>,
+    /// The attributes.
+    pub attributes: Vec<(Vec<u8>, Vec<u8>)>,
+    /// The extra data, which is the new key of `Rename` and the offset of `OverwriteValue`.
+    pub exdata: Vec<u8>,
+}
+
+impl TxRecord {
+    /// Get the key as a string without the terminating null.
+    pub fn key_str(&self) -> String {
+        bytes_to_string(&self.key)
+    }
+
+    /// Get the value as a string without the terminating null.
+    pub fn value_str(&self) -> Option<String> {
+        self.value.as_ref().map(|v| bytes_to_string(v))
+    }
+
+    /// Get the new key of a `Rename` record.
+    pub fn new_key(&self) -> Option<String> {
+        if self.operation == TxOperation::Rename {
+            Some(bytes_to_string(&self.exdata))
+        } else {
+            None
+        }
+    }
+}
+
+/// Converts bytes written by the C-API to a string without the terminating null.
+pub(crate) fn bytes_to_string(bytes: &[u8]) -> String {
+    let bytes = bytes.strip_suffix(&[0]).unwrap_or(bytes);
+    String::from_utf8_lossy(bytes).into_owned()
+}
+
+fn read_u64(buf: &[u8], pos: usize) -> u64 {
+    let mut b = [0u8; 8];
+    b.copy_from_slice(&buf[pos..pos + 8]);
+    u64::from_ne_bytes(b)
+}
+
+fn read_i64(buf: &[u8], pos: usize) -> i64 {
+    let mut b = [0u8; 8];
+    b.copy_from_slice(&buf[pos..pos + 8]);
+    i64::from_ne_bytes(b)
+}
+
+/// Gets the data region at pos and length in a command.
+fn region(command: &[u8], pos: i64, length: u64) -> Result<&[u8], &'static str> {
+    if length == 0 {
+        return Ok(&[]);
+    }
+    let start = usize::try_from(pos).map_err(|_| "broken data position")?;
+    let end = start
+        .checked_add(usize::try_from(length).map_err(|_| "broken data length")?)
+        .ok_or("broken data length")?;
+    command.get(start..end).ok_or("data is out of the record")
+}
+
+/// Parses the subkeys data of a command.
+pub(crate) fn parse_subkeys(data: &[u8]) -> Result<Vec<Vec<u8>>, &'static str> {
+    let mut subkeys = Vec::new();
+    if data.is_empty() {
+        return Ok(subkeys);
+    }
+    let mut cur = Cursor { data, pos: 0 };
+    let count = cur.u64()?;
+    for _ in 0..count {
+        let length = cur.u64()?;
+        subkeys.push(cur.bytes(length)?.to_vec());
+    }
+    Ok(subkeys)
+}
+
+/// Parses the attributes data of a command.
+pub(crate) fn parse_attributes(data: &[u8]) -> Result<RawAttributes, &'static str> {
+    let mut attrs = Vec::new();
+    if data.is_empty() {
+        return Ok(attrs);
+    }
+    let mut cur = Cursor { data, pos: 0 };
+    let count = cur.u64()?;
+    for _ in 0..count {
+        let key_length = cur.u64()?;
+        let val_length = cur.u64()?;
+        let key = cur.bytes(key_length)?.to_vec();
+        let val = cur.bytes(val_length)?.to_vec();
+        attrs.push((key, val));
+    }
+    Ok(attrs)
+}
+
+struct Cursor<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn u64(&mut self) -> Result<u64, &'static str> {
+        let bytes = self.bytes(8)?;
+        Ok(read_u64(bytes, 0))
+    }
+
+    fn bytes(&mut self, length: u64) -> Result<&'a [u8], &'static str> {
+        let length = usize::try_from(length).map_err(|_| "broken length")?;
+        let end = self.pos.checked_add(length).ok_or("broken length")?;
+        let bytes = self
+            .data
+            .get(self.pos..end)
+            .ok_or("length is out of the data")?;
+        self.pos = end;
+        Ok(bytes)
+    }
+}
+
+/// Parses a record from its head and command bytes.
+pub(crate) fn parse_record(
+    offset: u64,
+    head: &[u8; HEAD_SIZE],
+    command: &[u8],
+) -> Result<TxRecord, &'static str> {
+    if command.len() < COMMAND_SIZE {
+        return Err("command is too short");
+    }
+    let tv_sec = read_i64(head, 8);
+    let tv_nsec = read_i64(head, 16);
+    let timestamp = UNIX_EPOCH
+        + Duration::new(
+            u64::try_from(tv_sec).unwrap_or(0),
+            u32::try_from(tv_nsec).unwrap_or(0).min(999_999_999),
+        );
+    let operation = TxOperation::from_type(read_i64(command, 0)).ok_or("unknown operation type")?;
+    let lengths: Vec<u64> = (0..5).map(|i| read_u64(command, 8 + i * 8)).collect();
+    let positions: Vec<i64> = (0..5).map(|i| read_i64(command, 48 + i * 8)).collect();
+    let key = region(command, positions[0], lengths[0])?.to_vec();
+    let value = match lengths[1] {
+        0 => None,
+        _ => Some(region(command, positions[1], lengths[1])?.to_vec()),
+    };
+    let subkeys = parse_subkeys(region(command, positions[2], lengths[2])?)?;
+    let attributes = parse_attributes(region(command, positions[3], lengths[3])?)?;
+    let exdata = region(command, positions[4], lengths[4])?.to_vec();
+    Ok(TxRecord {
+        offset,
+        end_offset: offset + (HEAD_SIZE + command.len()) as u64,
+        timestamp,
+        operation,
+        key,
+        value,
+        subkeys,
+        attributes,
+        exdata,
+    })
+}
+
+/// Reads a record from a reader.
+///
+/// Returns `Ok(None)` at the end of the data or when the record is truncated.
+pub(crate) fn read_record<R: Read>(
+    reader: &mut R,
+    offset: u64,
+) -> Result<Option<TxRecord>, &'static str> {
+    let mut head = [0u8; HEAD_SIZE];
+    if !read_full(reader, &mut head)? {
+        return Ok(None);
+    }
+    let length = read_u64(&head, 0);
+    if length < COMMAND_SIZE as u64 || length > MAX_COMMAND_SIZE {
+        return Err("broken record length");
+    }
+    // read the command as far as the data goes, so a broken length can not allocate more than
+    // the rest of the file.
+    let mut command = Vec::new();
+    reader
+        .take(length)
+        .read_to_end(&mut command)
+        .map_err(|_| "Failed to read the transaction file")?;
+    if (command.len() as u64) < length {
+        return Ok(None);
+    }
+    parse_record(offset, &head, &command).map(Some)
+}
+
+/// Fills the buffer, or returns false if the data ends before it is filled.
+fn read_full<R: Read>(reader: &mut R, buf: &mut [u8]) -> Result<bool, &'static str> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => return Ok(false),
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(_) => return Err("Failed to read the transaction file"),
+        }
+    }
+    Ok(true)
+}
+
+/// Serializes the subkeys data of a command.
+#[cfg(feature = "libk2hash")]
+pub(crate) fn serialize_subkeys(subkeys: &[Vec<u8>]) -> Vec<u8> {
+    let mut data = Vec::new();
+    if subkeys.is_empty() {
+        return data;
+    }
+    data.extend_from_slice(&(subkeys.len() as u64).to_ne_bytes());
+    for subkey in subkeys {
+        data.extend_from_slice(&(subkey.len() as u64).to_ne_bytes());
+        data.extend_from_slice(subkey);
+    }
+    data
+}
+
+/// Serializes the attributes data of a command.
+#[cfg(feature = "libk2hash")]
+pub(crate) fn serialize_attributes(attrs: &[(Vec<u8>, Vec<u8>)]) -> Vec<u8> {
+    let mut data = Vec::new();
+    if attrs.is_empty() {
+        return data;
+    }
+    data.extend_from_slice(&(attrs.len() as u64).to_ne_bytes());
+    for (key, val) in attrs {
+        data.extend_from_slice(&(key.len() as u64).to_ne_bytes());
+        data.extend_from_slice(&(val.len() as u64).to_ne_bytes());
+        data.extend_from_slice(key);
+        data.extend_from_slice(val);
+    }
+    data
+}
+
+/// Serializes a record in the transaction file layout.
+#[cfg(feature = "libk2hash")]
+pub(crate) fn serialize_record(record: &TxRecord) -> Vec<u8> {
+    let op_type: i64 = match record.operation {
+        TxOperation::SetAll => 1,
+        TxOperation::ReplaceValue => 2,
+        TxOperation::ReplaceSubkeys => 3,
+        TxOperation::ReplaceAttributes => 4,
+        TxOperation::Remove => 5,
+        TxOperation::OverwriteValue => 6,
+        TxOperation::Rename => 7,
+    };
+    let value = record.value.as_deref().unwrap_or(&[]);
+    let subkeys = serialize_subkeys(&record.subkeys);
+    let attrs = serialize_attributes(&record.attributes);
+    let regions: [&[u8]; 5] = [&record.key, value, &subkeys, &attrs, &record.exdata];
+
+    let mut command = Vec::with_capacity(COMMAND_SIZE);
+    command.extend_from_slice(&op_type.to_ne_bytes());
+    for r in regions.iter() {
+        command.extend_from_slice(&(r.len() as u64).to_ne_bytes());
+    }
+    let mut pos = COMMAND_SIZE as i64;
+    for r in regions.iter() {
+        command.extend_from_slice(&pos.to_ne_bytes());
+        pos += r.len() as i64;
+    }
+    for r in regions.iter() {
+        command.extend_from_slice(r);
+    }
+
+    let since = record
+        .timestamp
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let mut data = Vec::with_capacity(HEAD_SIZE + command.len());
+    data.extend_from_slice(&(command.len() as u64).to_ne_bytes());
+    data.extend_from_slice(&(since.as_secs() as i64).to_ne_bytes());
+    data.extend_from_slice(&(since.subsec_nanos() as i64).to_ne_bytes());
+    data.extend_from_slice(&command);
+    data
+}
+
+/// TxLogReader iterates records in a k2hash transaction file.
+///
+/// A truncated record at the tail, which is being written by another process, ends the
+/// iteration without an error. In follow mode the reader waits for the record to be
+/// completed instead, like `tail -f`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use k2hash_rust::TxLogReader;
+/// let reader = TxLogReader::open("test.log").expect("open failed");
+/// for record in reader {
+///     let record = record.expect("broken record");
+///     println!("{:?} {}", record.operation, record.key_str());
+/// }
+/// ```
+pub struct TxLogReader<R> {
+    reader: R,
+    offset: u64,
+    follow: Option<Duration>,
+    truncated: bool,
+    failed: bool,
+}
+
+impl TxLogReader<BufReader<File>> {
+    /// Open a transaction file.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, &'static str> {
+        let file = File::open(path).map_err(|_| "Failed to open the transaction file")?;
+        Ok(TxLogReader::new(BufReader::new(file)))
+    }
+}
+
+impl<R: Read + Seek> TxLogReader<R> {
+    /// Create a new TxLogReader reading from the current position of the reader.
+    pub fn new(mut reader: R) -> Self {
+        let offset = reader.stream_position().unwrap_or(0);
+        TxLogReader {
+            reader,
+            offset,
+            follow: None,
+            truncated: false,
+            failed: false,
+        }
+    }
+
+    /// Follow the growing file, polling it every interval at the end.
+    pub fn follow(mut self, interval: Duration) -> Self {
+        self.follow = Some(interval);
+        self
+    }
+
+    /// Move to a byte offset, which should be the `end_offset` of a record.
+    pub fn seek_to(&mut self, offset: u64) -> Result<(), &'static str> {
+        self.reader
+            .seek(SeekFrom::Start(offset))
+            .map_err(|_| "Failed to seek the transaction file")?;
+        self.offset = offset;
+        self.truncated = false;
+        self.failed = false;
+        Ok(())
+    }
+
+    /// Get the byte offset of the next record.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Check if the iteration ended at a truncated record.
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+}
+
+impl<R: Read + Seek> Iterator for TxLogReader<R> {
+    type Item = Result<TxRecord, &'static str>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        loop {
+            match read_record(&mut self.reader, self.offset) {
+                Ok(Some(record)) => {
+                    self.offset = record.end_offset;
+                    self.truncated = false;
+                    return Some(Ok(record));
+                }
+                Ok(None) => {
+                    let end = self.reader.stream_position().unwrap_or(self.offset);
+                    self.truncated = end > self.offset;
+                    // rewind to the record top to read it again when it is completed.
+                    if self.reader.seek(SeekFrom::Start(self.offset)).is_err() {
+                        self.failed = true;
+                        return Some(Err("Failed to seek the transaction file"));
+                    }
+                    match self.follow {
+                        Some(interval) => thread::sleep(interval),
+                        None => return None,
+                    }
+                }
+                Err(e) => {
+                    self.failed = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
+}
+
+/// The largest value an `OverwriteValue` record may build, which bounds the offset read from
+/// the file.
+#[cfg(feature = "libk2hash")]
+const MAX_OVERWRITE_SIZE: usize = 256 * 1024 * 1024;
+
+/// Builds the value of a key after an `OverwriteValue` record is applied to its current value.
+#[cfg(feature = "libk2hash")]
+pub(crate) fn overwrite_value(current: &[u8], record: &TxRecord) -> Result<Vec<u8>, &'static str> {
+    let mut offset_bytes = [0u8; 8];
+    let n = record.exdata.len().min(8);
+    offset_bytes[..n].copy_from_slice(&record.exdata[..n]);
+    let data = record.value.as_deref().unwrap_or_default();
+    let end = usize::try_from(u64::from_ne_bytes(offset_bytes))
+        .ok()
+        .and_then(|offset| offset.checked_add(data.len()))
+        .filter(|end| *end <= MAX_OVERWRITE_SIZE.max(current.len()))
+        .ok_or("overwrite offset is out of range")?;
+    let mut value = current.to_vec();
+    if value.len() < end {
+        value.resize(end, 0);
+    }
+    value[end - data.len()..end].copy_from_slice(data);
+    Ok(value)
+}
+
+/// Reads the offset kept in a checkpoint file, or `None` if the file does not exist yet.
+#[cfg(feature = "libk2hash")]
+pub(crate) fn read_checkpoint(path: &str) -> Result<Option<u64>, &'static str> {
+    match std::fs::read_to_string(path) {
+        Ok(text) => text
+            .trim()
+            .parse::<u64>()
+            .map(Some)
+            .map_err(|_| "broken checkpoint file"),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(_) => Err("Failed to read the checkpoint file"),
+    }
+}
+
+/// Replaces the offset kept in a checkpoint file by writing a temporary file and renaming it,
+/// so a crash leaves either the old or the new offset.
+#[cfg(feature = "libk2hash")]
+pub(crate) fn write_checkpoint(path: &str, offset: u64) -> Result<(), &'static str> {
+    let tmp = format!("{}.tmp", path);
+    std::fs::write(&tmp, offset.to_string())
+        .and_then(|_| std::fs::rename(&tmp, path))
+        .map_err(|_| "Failed to write the checkpoint file")
+}
+
+/// ApplyTxOptions holds the options of `K2hash::apply_tx_log`.
+///
+/// # Examples
+///
+/// ```
+/// use k2hash_rust::ApplyTxOptions;
+/// let options = ApplyTxOptions::new()
+///     .checkpoint("replica.checkpoint".to_string()) // a file next to the replica
+///     .dry_run(true);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ApplyTxOptions {
+    pub(crate) start_offset: u64,
+    pub(crate) checkpoint: Option<String>,
+    pub(crate) dry_run: bool,
+}
+
+impl ApplyTxOptions {
+    /// Create a new ApplyTxOptions which applies a whole transaction file.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the byte offset to resume from, which is the `end_offset` of a previous report.
+    pub fn start_offset(mut self, offset: u64) -> Self {
+        self.start_offset = offset;
+        self
+    }
+
+    /// Set the sidecar file in which the offset to resume from is kept.
+    ///
+    /// A call resumes after the records applied by the previous calls with the same file, so
+    /// applying a transaction file again does not replay it. The larger of the stored offset
+    /// and `start_offset` is used. The file lives outside the database, so it shows up in no
+    /// key listing and fires no listener.
+    ///
+    /// Replay is at-least-once: the file is replaced after each record, not atomically with
+    /// it, so a crash between the two replays the last record on the next call.
+    pub fn checkpoint(mut self, path: String) -> Self {
+        self.checkpoint = Some(path);
+        self
+    }
+
+    /// Set the dry-run flag which only reports what would change.
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+}
+
+/// TxChange represents a record which changed the database, or would change it in dry-run mode.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TxChange {
+    /// The byte offset of the record in the file.
+    pub offset: u64,
+    /// The operation type.
+    pub operation: TxOperation,
+    /// The key without the terminating null.
+    pub key: String,
+}
+
+/// ApplyTxReport is the result of `K2hash::apply_tx_log`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ApplyTxReport {
+    /// The records which changed the database, or would change it in dry-run mode.
+    pub changes: Vec<TxChange>,
+    /// The number of records which did not change the database, such as setting the same value.
+    pub unchanged: usize,
+    /// The byte offset to resume from in the next call.
+    pub end_offset: u64,
+    /// Whether the file ended at a truncated record which is still being written.
+    pub truncated: bool,
+}
+
+//
+// Local variables:
+// tab-width: 4
+// c-basic-offset: 4
+// End:
+// vim600: expandtab sw=4 ts=4 fdm=marker
+// vim<600: expandtab sw=4 ts=4
+//
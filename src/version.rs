@@ -0,0 +1,111 @@
+//
+// k2hash_rust
+//
+// Copyright 2025 LY Corporation.
+//
+// Rust driver for k2hash that is a NoSQL Key Value Store(KVS) library.
+// For k2hash, see https://github.com/yahoojapan/k2hash for the details.
+//
+// For the full copyright and license information, please view
+// the license file that was distributed with this source code.
+//
+// AUTHOR:   Hirotaka Wakabayashi
+// CREATE:   Fri, 17 Jul 2025
+// REVISION:
+//
+
+//! Version of the k2hash C-library.
+
+use std::fmt;
+use std::str::FromStr;
+
+/// The minimum libk2hash version supported by this crate.
+///
+/// This is the k2hash-dev release the CI installs from packagecloud.io, which has all the
+/// C-APIs this crate calls. `K2hash::open` and `K2hash::open_mem` refuse older libraries, and
+/// only warn if the version output of the library cannot be parsed.
+pub const MIN_LIBRARY_VERSION: Version = Version {
+    major: 1,
+    minor: 0,
+    patch: 93,
+    build: String::new(),
+};
+
+/// Version represents the version of the k2hash C-library.
+///
+/// # Examples
+///
+/// ```
+/// use k2hash_rust::Version;
+/// let version: Version = "1.0.93 (commit: 1a2b3c4)".parse().expect("parse failed");
+/// assert_eq!((version.major, version.minor, version.patch), (1, 0, 93));
+/// assert_eq!(version.build, "(commit: 1a2b3c4)");
+/// assert_eq!(version.to_string(), "1.0.93");
+/// assert!("unknown".parse::<Version>().is_err(), "A banner without a version should fail");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Version {
+    /// The major version.
+    pub major: u32,
+    /// The minor version.
+    pub minor: u32,
+    /// The patch version.
+    pub patch: u32,
+    /// The build information which follows the version number, such as the commit hash.
+    pub build: String,
+}
+
+impl FromStr for Version {
+    type Err = &'static str;
+
+    /// Parses `major.minor.patch` followed by optional build information.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (number, build) = s.split_once(char::is_whitespace).unwrap_or((s, ""));
+        let mut parts = number.trim_start_matches(['v', 'V']).split('.');
+        let mut next = || -> Result<u32, &'static str> {
+            parts
+                .next()
+                .ok_or("version should be major.minor.patch")?
+                .parse()
+                .map_err(|_| "version should be numbers")
+        };
+        let (major, minor, patch) = (next()?, next()?, next()?);
+        Ok(Version {
+            major,
+            minor,
+            patch,
+            build: build.trim().to_string(),
+        })
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// Finds the version in the banner printed by `k2h_print_version`.
+///
+/// The version is the first word which parses as `major.minor.patch`, so the digit of a
+/// name such as `K2HASH` is not taken for it.
+#[cfg(feature = "libk2hash")]
+pub(crate) fn parse_version_banner(banner: &str) -> Option<Version> {
+    banner.lines().find_map(|line| {
+        line.char_indices()
+            .filter(|(i, c)| {
+                !c.is_whitespace() && (*i == 0 || line[..*i].ends_with(char::is_whitespace))
+            })
+            .find_map(|(i, _)| line[i..].parse().ok())
+    })
+}
+
+//
+// Local variables:
+// tab-width: 4
+// c-basic-offset: 4
+// End:
+// vim600: expandtab sw=4 ts=4 fdm=marker
+// vim<600: expandtab sw=4 ts=4
+//
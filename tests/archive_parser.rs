@@ -0,0 +1,124 @@
+//
+// k2hash_rust
+//
+// Copyright 2025 LY Corporation.
+//
+// Rust driver for k2hash that is a NoSQL Key Value Store(KVS) library.
+// For k2hash, see https://github.com/yahoojapan/k2hash for the details.
+//
+// For the full copyright and license information, please view
+// the license file that was distributed with this source code.
+//
+// AUTHOR:   Hirotaka Wakabayashi
+// CREATE:   Fri, 17 Jul 2025
+// REVISION:
+//
+
+#![cfg(feature = "archive-parser")]
+
+use k2hash_rust::{ArchiveEntry, ArchiveParser};
+#[cfg(feature = "libk2hash")]
+use k2hash_rust::{ArchiveWriter, K2hash};
+
+/// An archive of "hello" with the value "world", the subkey "sub" and the attribute "owner",
+/// and of "sub" with the value "val".
+///
+/// The file is hand-built after the transaction file layout of an LP64 writer, not written by
+/// libk2hash, so the parser is tested on a machine without libk2hash.
+const ARCHIVE_FIXTURE: &str = "tests/fixtures/archive_basic.k2har";
+
+#[test]
+fn test_archiveparser_fixture() {
+    let entries: Vec<ArchiveEntry> = ArchiveParser::open(ARCHIVE_FIXTURE)
+        .expect("Open archive failed")
+        .map(|e| e.expect("Parse archive failed"))
+        .collect();
+    assert_eq!(entries.len(), 2, "The archive should have two entries");
+    assert_eq!(entries[0].key_str(), "hello");
+    assert_eq!(entries[0].value_str(), "world");
+    assert_eq!(entries[0].subkey_strs(), vec!["sub".to_string()]);
+    assert_eq!(
+        entries[0].attribute_strs(),
+        vec![("owner".to_string(), "alice".to_string())]
+    );
+    assert_eq!(entries[1].key_str(), "sub");
+    assert_eq!(entries[1].value_str(), "val");
+    assert_eq!(entries[1].offset, entries[0].offset + 180);
+}
+
+#[test]
+#[cfg(feature = "libk2hash")]
+fn test_archiveparser_dump_to_file() {
+    let db = K2hash::open_mem().expect("open_mem failed");
+    assert!(db.set("hello", "world").is_ok(), "Set operation failed");
+    assert!(
+        db.add_subkey("hello", "sub", "val").is_ok(),
+        "Add subkey operation failed"
+    );
+    let k2h_archive = "test_archiveparser_dump_to_file.k2har";
+    assert!(
+        db.dump_to_file(k2h_archive).is_ok(),
+        "Dump to file operation failed"
+    );
+    let entries: Vec<ArchiveEntry> = ArchiveParser::open(k2h_archive)
+        .expect("Open archive failed")
+        .map(|e| e.expect("Parse archive failed"))
+        .collect();
+    let hello = entries
+        .iter()
+        .find(|e| e.key_str() == "hello")
+        .expect("The archive should have hello");
+    assert_eq!(hello.value_str(), "world");
+    assert_eq!(hello.subkey_strs(), vec!["sub".to_string()]);
+    let sub = entries
+        .iter()
+        .find(|e| e.key_str() == "sub")
+        .expect("The archive should have the subkey");
+    assert_eq!(sub.value_str(), "val");
+}
+
+#[test]
+#[cfg(feature = "libk2hash")]
+fn test_archiveparser_archive_writer() {
+    let db = K2hash::open_mem().expect("open_mem failed");
+    assert!(db.set("hello", "world").is_ok(), "Set operation failed");
+    assert!(
+        db.set_attribute("hello", "owner", "alice").is_ok(),
+        "Set attribute operation failed"
+    );
+    let mut archive = Vec::new();
+    ArchiveWriter::new(&db)
+        .write_to(&mut archive)
+        .expect("Export failed");
+    let entries: Vec<ArchiveEntry> = ArchiveParser::new(archive.as_slice())
+        .map(|e| e.expect("Parse archive failed"))
+        .collect();
+    assert_eq!(entries.len(), 1, "The archive should have one entry");
+    assert_eq!(entries[0].key_str(), "hello");
+    assert_eq!(entries[0].value_str(), "world");
+    assert!(
+        entries[0]
+            .attribute_strs()
+            .contains(&("owner".to_string(), "alice".to_string())),
+        "The entry should have the attribute"
+    );
+    // a broken archive is reported as an error.
+    let mut broken = archive.clone();
+    broken[0] = 0;
+    assert!(
+        ArchiveParser::new(broken.as_slice())
+            .next()
+            .expect("The broken record should be reported")
+            .is_err(),
+        "A broken record should be an error"
+    );
+}
+
+//
+// Local variables:
+// tab-width: 4
+// c-basic-offset: 4
+// End:
+// vim600: expandtab sw=4 ts=4 fdm=marker
+// vim<600: expandtab sw=4 ts=4
+//
@@ -15,8 +15,18 @@
 //  
 // 
 
-use k2hash_rust::{DumpLevel, K2hash, K2hashKey, KeyQueue, KeyQueueBuilder, Queue, QueueBuilder};
+#![cfg(feature = "libk2hash")]
+
+use k2hash_rust::{
+    ApplyTxOptions, ArchiveReader, ArchiveWriter, BackupOptions, CompactOptions, DelayedQueue,
+    DumpLevel, K2hash, K2hashKey, KeyQueue, KeyQueueBuilder, PriorityQueueBuilder, Queue,
+    QueueBuilder, QueueKind, ReliableQueue, TxListener, TxLogReader, TxOperation, TxRecord,
+    MIN_LIBRARY_VERSION,
+};
 use std::collections::HashMap;
+use std::os::fd::AsRawFd;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
 
 /// Test for k2hash handle
 #[test]
@@ -1162,6 +1172,1147 @@ fn test_k2hashkey_iterator() {
     assert_eq!(k2hkey.next(), None); // internally calls k2h_find_next, but no more keys are available, so returns None.
 }
 
+/// Test for ReliableQueue operations
+#[test]
+fn test_reliablequeue_ack() {
+    let db = K2hash::open_mem().expect("open_mem failed");
+    let q = ReliableQueue::new(db.handle(), "test_reliable".to_string(), 30)
+        .expect("ReliableQueue creation failed");
+    let id = q.put("hello").expect("Push operation failed");
+    assert!(
+        q.qsize() == 1,
+        "ReliableQueue size should be 1 after putting the value"
+    );
+    let (popped_id, value) = q.get().expect("Get operation failed or returned None");
+    assert_eq!(popped_id, id, "Get operation returned unexpected id");
+    assert_eq!(value, "hello", "Get operation returned unexpected value");
+    assert!(
+        q.qsize() == 0,
+        "ReliableQueue size should be 0 after getting the value"
+    );
+    assert!(q.ack(&popped_id).is_ok(), "Ack operation failed");
+    assert!(q.ack(&popped_id).is_err(), "Second ack should fail");
+    assert_eq!(
+        q.recover_expired().expect("Recover operation failed"),
+        0,
+        "Acknowledged item should not be recovered"
+    );
+    assert!(q.inflight_size() == 0, "In-flight entries should be swept");
+}
+
+#[test]
+fn test_reliablequeue_recover_expired() {
+    let db = K2hash::open_mem().expect("open_mem failed");
+    // zero lease makes the popped item expire immediately.
+    let q = ReliableQueue::new(db.handle(), "test_reliable".to_string(), 0)
+        .expect("ReliableQueue creation failed");
+    let id = q.put("hello").expect("Push operation failed");
+    assert!(q.get().is_some(), "Get operation failed or returned None");
+    assert!(
+        q.get().is_none(),
+        "ReliableQueue should be empty while the item is in-flight"
+    );
+    assert_eq!(
+        q.recover_expired().expect("Recover operation failed"),
+        1,
+        "Expired item should be recovered"
+    );
+    let (popped_id, value) = q.get().expect("Get operation failed or returned None");
+    assert_eq!(popped_id, id, "Recovered item should keep its id");
+    assert_eq!(value, "hello", "Recovered item should keep its value");
+    assert!(q.ack(&popped_id).is_ok(), "Ack operation failed");
+}
+
+#[test]
+fn test_reliablequeue_recover_interrupted_get() {
+    let db = K2hash::open_mem().expect("open_mem failed");
+    let q = ReliableQueue::new(db.handle(), "test_reliable_crash".to_string(), 0)
+        .expect("ReliableQueue creation failed");
+    let ready = KeyQueue::new(
+        db.handle(),
+        true,
+        Some("test_reliable_crash.ready.".to_string()),
+        None,
+        None,
+    )
+    .expect("KeyQueue creation failed");
+    let inflight = Queue::new(
+        db.handle(),
+        true,
+        Some("test_reliable_crash.inflight.".to_string()),
+        None,
+        None,
+    )
+    .expect("Queue creation failed");
+    // leave the pending lease and the in-flight entry of a get which stopped before or after
+    // popping.
+    let lease = |id: &str| {
+        assert!(
+            db.set(
+                &format!("test_reliable_crash.lease.{}", id),
+                "token pending"
+            )
+            .is_ok(),
+            "Set operation failed"
+        );
+        assert!(
+            inflight.put(&format!("0 {} token", id)).is_ok(),
+            "Push operation failed"
+        );
+    };
+    let before_pop = q.put("hello").expect("Push operation failed");
+    lease(&before_pop);
+    assert_eq!(
+        q.recover_expired().expect("Recover operation failed"),
+        0,
+        "Ready item should not be put back"
+    );
+    assert_eq!(q.qsize(), 1, "Ready item should not be put back twice");
+    assert_eq!(q.inflight_size(), 0, "The lease should be released");
+    let (popped_id, _) = q.get().expect("Get operation failed or returned None");
+    assert_eq!(popped_id, before_pop, "Recovered item should keep its id");
+    assert!(q.ack(&popped_id).is_ok(), "Ack operation failed");
+    let after_pop = q.put("hello").expect("Push operation failed");
+    lease(&after_pop);
+    assert!(ready.get().is_some(), "Pop operation failed");
+    assert_eq!(
+        q.recover_expired().expect("Recover operation failed"),
+        1,
+        "Interrupted item should be recovered"
+    );
+    let (popped_id, value) = q.get().expect("Get operation failed or returned None");
+    assert_eq!(popped_id, after_pop, "Recovered item should keep its id");
+    assert_eq!(value, "hello", "Recovered item should keep its value");
+    assert!(q.get().is_none(), "ReliableQueue should be empty");
+}
+
+#[test]
+fn test_reliablequeue_concurrent_consumers() {
+    let db = K2hash::open_mem().expect("open_mem failed");
+    let first = ReliableQueue::new(db.handle(), "test_reliable_race".to_string(), 0)
+        .expect("ReliableQueue creation failed");
+    let second = ReliableQueue::new(db.handle(), "test_reliable_race".to_string(), 0)
+        .expect("ReliableQueue creation failed");
+    let inflight = Queue::new(
+        db.handle(),
+        true,
+        Some("test_reliable_race.inflight.".to_string()),
+        None,
+        None,
+    )
+    .expect("Queue creation failed");
+    let taken = first.put("hello").expect("Push operation failed");
+    let left = first.put("world").expect("Push operation failed");
+    // the first consumer leases the head, then the second one pops and acks it first.
+    assert!(
+        db.set(
+            &format!("test_reliable_race.lease.{}", taken),
+            "first pending"
+        )
+        .is_ok(),
+        "Set operation failed"
+    );
+    assert!(
+        inflight.put(&format!("0 {} first", taken)).is_ok(),
+        "Push operation failed"
+    );
+    let (popped_id, _) = second.get().expect("Get operation failed or returned None");
+    assert_eq!(popped_id, taken, "Get operation returned unexpected id");
+    assert!(second.ack(&popped_id).is_ok(), "Ack operation failed");
+    // the acknowledged item is not delivered again.
+    assert_eq!(
+        first.recover_expired().expect("Recover operation failed"),
+        0,
+        "Acknowledged item should not be recovered"
+    );
+    assert_eq!(
+        first.inflight_size(),
+        0,
+        "In-flight entries should be swept"
+    );
+    let (popped_id, value) = first.get().expect("Get operation failed or returned None");
+    assert_eq!(popped_id, left, "Get operation returned unexpected id");
+    assert_eq!(value, "world", "Get operation returned unexpected value");
+    assert!(first.ack(&popped_id).is_ok(), "Ack operation failed");
+    assert!(first.get().is_none(), "ReliableQueue should be empty");
+}
+
+/// Test for PriorityQueue operations
+#[test]
+fn test_priorityqueue_get() {
+    let db = K2hash::open_mem().expect("open_mem failed");
+    let q = PriorityQueueBuilder::new(db.handle())
+        .prefix("test_priority".to_string())
+        .levels(3)
+        .build()
+        .expect("PriorityQueue creation failed");
+    assert!(q.put(0, "low").is_ok(), "Push operation failed");
+    assert!(q.put(1, "middle").is_ok(), "Push operation failed");
+    assert!(q.put(2, "high").is_ok(), "Push operation failed");
+    assert!(
+        q.put(3, "none").is_err(),
+        "Push to an unknown priority should fail"
+    );
+    assert_eq!(q.qsize(), 3, "PriorityQueue size should be 3");
+    assert_eq!(q.level_qsize(1), Some(1), "Level size should be 1");
+    assert_eq!(q.level_qsize(3), None, "Unknown level should have no size");
+    assert_eq!(q.get(), Some((2, "high".to_string())));
+    assert_eq!(q.get(), Some((1, "middle".to_string())));
+    assert_eq!(q.get(), Some((0, "low".to_string())));
+    assert_eq!(q.get(), None);
+    assert!(q.empty(), "PriorityQueue should be empty");
+}
+
+#[test]
+fn test_priorityqueue_weights() {
+    let db = K2hash::open_mem().expect("open_mem failed");
+    let q = PriorityQueueBuilder::new(db.handle())
+        .prefix("test_priority".to_string())
+        .levels(2)
+        .weights(vec![1, 2])
+        .build()
+        .expect("PriorityQueue creation failed");
+    for _ in 0..3 {
+        assert!(q.put(0, "low").is_ok(), "Push operation failed");
+        assert!(q.put(1, "high").is_ok(), "Push operation failed");
+    }
+    let served: Vec<usize> = (0..3)
+        .map(|_| q.get().expect("Get operation failed").0)
+        .collect();
+    // the low priority level is served once in every three gets.
+    assert_eq!(
+        served,
+        vec![1, 0, 1],
+        "Weighted scheduling served unexpected levels"
+    );
+}
+
+/// Test for DelayedQueue operations
+#[test]
+fn test_delayedqueue_put_delayed() {
+    let db = K2hash::open_mem().expect("open_mem failed");
+    let q = DelayedQueue::new(db.handle(), "test_delayed".to_string())
+        .expect("DelayedQueue creation failed");
+    assert!(
+        q.next_due().is_none(),
+        "Empty DelayedQueue should have no due time"
+    );
+    assert!(
+        q.put_delayed("later", Duration::from_secs(3600)).is_ok(),
+        "Push operation failed"
+    );
+    assert_eq!(q.get(), None, "No value should be due yet");
+    let now = SystemTime::now();
+    assert!(
+        q.put_at("late", now - Duration::from_secs(1)).is_ok(),
+        "Push operation failed"
+    );
+    assert!(
+        q.put_at("early", now - Duration::from_secs(2)).is_ok(),
+        "Push operation failed"
+    );
+    assert_eq!(q.qsize(), 3, "DelayedQueue size should be 3");
+    let next_due = q.next_due().expect("DelayedQueue should have a due time");
+    assert!(
+        next_due < now,
+        "The earliest due time should be the past one"
+    );
+    assert_eq!(q.get(), Some("early".to_string()));
+    assert_eq!(q.get(), Some("late".to_string()));
+    assert_eq!(q.get(), None, "The long delay should not be due yet");
+    assert_eq!(
+        q.qsize(),
+        1,
+        "DelayedQueue should keep the value not due yet"
+    );
+}
+
+#[test]
+fn test_delayedqueue_shared_prefix() {
+    let db = K2hash::open_mem().expect("open_mem failed");
+    let q1 = DelayedQueue::new(db.handle(), "test_delayed_shared".to_string())
+        .expect("DelayedQueue creation failed");
+    let q2 = DelayedQueue::new(db.handle(), "test_delayed_shared".to_string())
+        .expect("DelayedQueue creation failed");
+    assert!(q1.empty(), "DelayedQueue should be empty");
+    assert!(q2.put("first").is_ok(), "Push operation failed");
+    assert_eq!(
+        q1.qsize(),
+        1,
+        "The put of the other DelayedQueue should be seen"
+    );
+    assert!(q1.put("second").is_ok(), "Push operation failed");
+    assert_eq!(q2.get(), Some("first".to_string()));
+    assert_eq!(q1.get(), Some("second".to_string()));
+    assert!(q1.empty(), "DelayedQueue should be empty");
+    assert!(q2.empty(), "DelayedQueue should be empty");
+}
+
+#[test]
+fn test_queue_dead_letters() {
+    let db = K2hash::open_mem().expect("open_mem failed");
+    let q = QueueBuilder::new(db.handle())
+        .prefix("test_dlq".to_string())
+        .max_attempts(2)
+        .build()
+        .expect("Queue creation failed");
+    assert!(q.put("hello").is_ok(), "Push operation failed");
+    let (value, attempts) = q.get_with_attempts().expect("Get operation failed");
+    assert_eq!(attempts, 0, "First delivery should have no failed attempts");
+    assert!(
+        !q.nack(&value, attempts).expect("Nack operation failed"),
+        "Value should be retried"
+    );
+    let (value, attempts) = q.get_with_attempts().expect("Get operation failed");
+    assert_eq!(
+        attempts, 1,
+        "Second delivery should have one failed attempt"
+    );
+    assert!(
+        q.nack(&value, attempts).expect("Nack operation failed"),
+        "Value should be dead-lettered"
+    );
+    assert!(q.empty(), "Queue should be empty after dead-lettering");
+    let dlq = q.dead_letters().expect("Dead-letter queue creation failed");
+    assert_eq!(dlq.qsize(), 1, "Dead-letter queue size should be 1");
+    assert_eq!(dlq.get(), Some("hello".to_string()));
+}
+
+#[test]
+fn test_keyqueue_dead_letters() {
+    let db = K2hash::open_mem().expect("open_mem failed");
+    let q = KeyQueueBuilder::new(db.handle())
+        .prefix("test_dlq".to_string())
+        .max_attempts(1)
+        .build()
+        .expect("KeyQueue creation failed");
+    assert!(q.put("hello", "world").is_ok(), "Push operation failed");
+    let (key, value, attempts) = q.get_with_attempts().expect("Get operation failed");
+    assert_eq!(attempts, 0, "First delivery should have no failed attempts");
+    assert!(
+        q.nack(&key, &value, attempts)
+            .expect("Nack operation failed"),
+        "Pair should be dead-lettered"
+    );
+    let dlq = q.dead_letters().expect("Dead-letter queue creation failed");
+    assert_eq!(dlq.get(), Some(("hello".to_string(), "world".to_string())));
+}
+
+#[test]
+fn test_keyqueue_dead_letters_after_retries() {
+    let db = K2hash::open_mem().expect("open_mem failed");
+    let q = KeyQueueBuilder::new(db.handle())
+        .prefix("test_dlq_retries".to_string())
+        .max_attempts(3)
+        .build()
+        .expect("KeyQueue creation failed");
+    assert!(q.put("hello", "world").is_ok(), "Push operation failed");
+    for expected in 0..2 {
+        let (key, value, attempts) = q.get_with_attempts().expect("Get operation failed");
+        assert_eq!(attempts, expected, "Attempts should count up on each nack");
+        assert!(
+            !q.nack(&key, &value, attempts)
+                .expect("Nack operation failed"),
+            "Pair should be retried"
+        );
+    }
+    let (key, value, attempts) = q.get_with_attempts().expect("Get operation failed");
+    assert_eq!(
+        attempts, 2,
+        "Third delivery should have two failed attempts"
+    );
+    assert!(
+        q.nack(&key, &value, attempts)
+            .expect("Nack operation failed"),
+        "Pair should be dead-lettered after three attempts"
+    );
+    assert!(q.empty(), "KeyQueue should be empty after dead-lettering");
+    let dlq = q.dead_letters().expect("Dead-letter queue creation failed");
+    assert_eq!(dlq.get(), Some(("hello".to_string(), "world".to_string())));
+    assert!(q.put("hello", "again").is_ok(), "Push operation failed");
+    let (_, _, attempts) = q.get_with_attempts().expect("Get operation failed");
+    assert_eq!(
+        attempts, 0,
+        "A new put of the key should start with no attempts"
+    );
+}
+
+// K2hash::list_queues
+#[test]
+fn test_k2hash_list_queues() {
+    let db = K2hash::open_mem().expect("open_mem failed");
+    assert!(
+        db.list_queues()
+            .expect("List queues operation failed")
+            .is_empty(),
+        "Empty database should have no queues"
+    );
+    let q1 = Queue::new(db.handle(), true, Some("test_q1".to_string()), None, None)
+        .expect("Queue creation failed");
+    let q2 = Queue::new(db.handle(), false, Some("test_q2".to_string()), None, None)
+        .expect("Queue creation failed");
+    assert!(q1.put("hello").is_ok(), "Push operation failed");
+    assert!(q2.put("hello").is_ok(), "Push operation failed");
+    assert!(q2.put("world").is_ok(), "Push operation failed");
+    let kq = KeyQueue::new(db.handle(), true, Some("test_kq".to_string()), None, None)
+        .expect("KeyQueue creation failed");
+    assert!(kq.put("hello", "world").is_ok(), "Push operation failed");
+    let empty = Queue::new(db.handle(), true, Some("test_q3".to_string()), None, None)
+        .expect("Queue creation failed");
+    assert!(empty.close(), "Close operation failed");
+    let queues = db.list_queues().expect("List queues operation failed");
+    assert_eq!(queues.len(), 4, "Every opened queue should be listed once");
+    let found: HashMap<String, (QueueKind, bool, usize)> = queues
+        .into_iter()
+        .map(|q| {
+            (
+                String::from_utf8_lossy(&q.prefix).into_owned(),
+                (q.kind, q.fifo, q.qsize),
+            )
+        })
+        .collect();
+    assert_eq!(
+        found.get("test_q1"),
+        Some(&(QueueKind::Queue, true, 1)),
+        "test_q1 should be a FIFO queue with 1 element"
+    );
+    assert_eq!(
+        found.get("test_q2"),
+        Some(&(QueueKind::Queue, false, 2)),
+        "test_q2 should be a LIFO queue with 2 elements"
+    );
+    assert_eq!(
+        found.get("test_kq"),
+        Some(&(QueueKind::KeyQueue, true, 1)),
+        "test_kq should be a FIFO key queue with 1 element"
+    );
+    assert_eq!(
+        found.get("test_q3"),
+        Some(&(QueueKind::Queue, true, 0)),
+        "An empty queue should be listed"
+    );
+}
+
+// K2hash::transaction
+#[test]
+fn test_k2hash_transaction() {
+    let db = K2hash::open_mem().expect("open_mem failed");
+    assert!(db.handle() != 0, "Database handle should not be zero");
+    {
+        let tx = db
+            .transaction("test.log")
+            .expect("Begin transaction operation failed");
+        let fd = tx.fd().expect("Get transaction file descriptor failed");
+        assert!(
+            fd.as_raw_fd() >= 0,
+            "Transaction file descriptor should not be invalid"
+        );
+        let owned = tx
+            .try_clone_fd()
+            .expect("Clone transaction file descriptor failed");
+        assert!(
+            owned.as_raw_fd() != fd.as_raw_fd(),
+            "Cloned file descriptor should be a new one"
+        );
+        assert!(db.set("hello", "world").is_ok(), "Set operation failed");
+    }
+    assert!(
+        db.get_tx_file_fd().is_err(),
+        "Transaction should be stopped when the guard is dropped"
+    );
+    let tx = db
+        .transaction_builder("test.log")
+        .prefix("hello".to_string())
+        .expire_duration(60)
+        .begin()
+        .expect("Begin transaction operation failed");
+    assert!(tx.finish().is_ok(), "Stop transaction operation failed");
+}
+
+// TxLogReader
+#[test]
+fn test_txlogreader() {
+    let k2h_tx_log = "test_txlogreader.log";
+    let _ = std::fs::remove_file(k2h_tx_log);
+    let db = K2hash::open_mem().expect("open_mem failed");
+    {
+        let _tx = db
+            .transaction(k2h_tx_log)
+            .expect("Begin transaction operation failed");
+        assert!(db.set("hello", "world").is_ok(), "Set operation failed");
+        assert!(db.remove("hello").is_ok(), "Remove operation failed");
+    }
+    let mut reader = TxLogReader::open(k2h_tx_log).expect("Open transaction file failed");
+    let records: Vec<TxRecord> = reader
+        .by_ref()
+        .map(|r| r.expect("Read transaction record failed"))
+        .collect();
+    assert!(
+        !reader.is_truncated(),
+        "Transaction file should not be truncated"
+    );
+    assert!(
+        records
+            .iter()
+            .any(|r| r.key_str() == "hello" && r.value_str() == Some("world".to_string())),
+        "Transaction file should have the set record"
+    );
+    assert!(
+        records
+            .iter()
+            .any(|r| r.key_str() == "hello" && r.operation == TxOperation::Remove),
+        "Transaction file should have the remove record"
+    );
+    // reading from the last offset returns nothing.
+    let end = reader.offset();
+    assert!(reader.seek_to(end).is_ok(), "Seek operation failed");
+    assert!(
+        reader.next().is_none(),
+        "No record should follow the last one"
+    );
+}
+
+#[test]
+fn test_k2hash_apply_tx_log() {
+    let k2h_tx_log = "test_k2hash_apply_tx_log.log";
+    let _ = std::fs::remove_file(k2h_tx_log);
+    let primary = K2hash::open_mem().expect("open_mem failed");
+    {
+        let _tx = primary
+            .transaction(k2h_tx_log)
+            .expect("Begin transaction operation failed");
+        assert!(
+            primary.set("hello", "world").is_ok(),
+            "Set operation failed"
+        );
+        assert!(primary.set("bye", "world").is_ok(), "Set operation failed");
+        assert!(primary.remove("bye").is_ok(), "Remove operation failed");
+    }
+    let replica = K2hash::open_mem().expect("open_mem failed");
+    // a dry run changes nothing, but sees the earlier records of the same run.
+    let report = replica
+        .apply_tx_log(k2h_tx_log, ApplyTxOptions::new().dry_run(true))
+        .expect("Dry run failed");
+    assert!(
+        report
+            .changes
+            .iter()
+            .any(|c| c.operation == TxOperation::Remove && c.key == "bye"),
+        "Dry run should report removing the key set by an earlier record"
+    );
+    assert_eq!(replica.get("hello").unwrap_or(None), None);
+    // apply the file with a checkpoint, then applying it again replays nothing.
+    let checkpoint = "test_k2hash_apply_tx_log.checkpoint".to_string();
+    let _ = std::fs::remove_file(&checkpoint);
+    let report = replica
+        .apply_tx_log(
+            k2h_tx_log,
+            ApplyTxOptions::new().checkpoint(checkpoint.clone()),
+        )
+        .expect("Apply operation failed");
+    assert!(!report.changes.is_empty(), "Apply should report changes");
+    assert_eq!(replica.get("hello").unwrap(), Some("world".to_string()));
+    assert_eq!(replica.get("bye").unwrap_or(None), None);
+    let again = replica
+        .apply_tx_log(
+            k2h_tx_log,
+            ApplyTxOptions::new().checkpoint(checkpoint.clone()),
+        )
+        .expect("Apply operation failed");
+    assert!(
+        again.changes.is_empty(),
+        "Applying again should change nothing"
+    );
+    assert_eq!(again.unchanged, 0, "Applying again should read no record");
+    assert_eq!(again.end_offset, report.end_offset);
+    assert_eq!(
+        replica.get(&checkpoint).unwrap_or(None),
+        None,
+        "The checkpoint should not be stored as a key"
+    );
+    let _ = std::fs::remove_file(&checkpoint);
+    // resuming from the end offset reads nothing either.
+    let resumed = replica
+        .apply_tx_log(
+            k2h_tx_log,
+            ApplyTxOptions::new().start_offset(report.end_offset),
+        )
+        .expect("Apply operation failed");
+    assert_eq!(resumed.changes.len() + resumed.unchanged, 0);
+    assert_eq!(resumed.end_offset, report.end_offset);
+    // replaying from the top redoes the records and ends in the same state.
+    assert!(replica
+        .apply_tx_log(k2h_tx_log, ApplyTxOptions::new())
+        .is_ok());
+    assert_eq!(replica.get("hello").unwrap(), Some("world".to_string()));
+    assert_eq!(replica.get("bye").unwrap_or(None), None);
+}
+
+struct RecordingListener(Arc<Mutex<Vec<String>>>);
+
+impl TxListener for RecordingListener {
+    fn on_set(&self, key: &str, value: &str) {
+        self.0
+            .lock()
+            .unwrap()
+            .push(format!("set {} {}", key, value));
+    }
+    fn on_remove(&self, key: &str) {
+        self.0.lock().unwrap().push(format!("remove {}", key));
+    }
+    fn on_rename(&self, oldkey: &str, newkey: &str) {
+        self.0
+            .lock()
+            .unwrap()
+            .push(format!("rename {} {}", oldkey, newkey));
+    }
+    fn on_add_subkey(&self, key: &str, subkey: &str, _subval: &str) {
+        self.0
+            .lock()
+            .unwrap()
+            .push(format!("add_subkey {} {}", key, subkey));
+    }
+}
+
+#[test]
+fn test_k2hash_tx_listener() {
+    let db = K2hash::open_mem().expect("open_mem failed");
+    let events = Arc::new(Mutex::new(Vec::new()));
+    db.set_tx_listener(RecordingListener(events.clone()));
+    assert!(db.set("hello", "world").is_ok(), "Set operation failed");
+    assert!(
+        db.add_subkey("hello", "sub", "val").is_ok(),
+        "Add subkey operation failed"
+    );
+    assert!(db.rename("hello", "bye").is_ok(), "Rename operation failed");
+    assert!(db.remove("bye").is_ok(), "Remove operation failed");
+    db.clear_tx_listener();
+    assert!(db.set("hello", "again").is_ok(), "Set operation failed");
+    assert_eq!(
+        *events.lock().unwrap(),
+        vec![
+            "set hello world".to_string(),
+            "add_subkey hello sub".to_string(),
+            "rename hello bye".to_string(),
+            "remove bye".to_string(),
+        ]
+    );
+}
+
+#[test]
+fn test_archive_writer_reader() {
+    let src = K2hash::open_mem().expect("open_mem failed");
+    assert!(src.set("user.1", "alice").is_ok(), "Set operation failed");
+    assert!(src.set("user.2", "bob").is_ok(), "Set operation failed");
+    assert!(src.set("tmp.1", "scratch").is_ok(), "Set operation failed");
+    let mut archive = Vec::new();
+    let mut progress_calls = 0;
+    let summary = ArchiveWriter::new(&src)
+        .filter(|key| !key.starts_with("tmp."))
+        .on_progress(|_| progress_calls += 1)
+        .write_to(&mut archive)
+        .expect("Export failed");
+    assert_eq!(summary.records, 2, "Two keys should be exported");
+    assert_eq!(summary.filtered, 1, "One key should be filtered");
+    assert_eq!(summary.bytes, archive.len() as u64);
+    assert_eq!(progress_calls, 2, "Progress should be reported per key");
+
+    let dst = K2hash::open_mem().expect("open_mem failed");
+    let summary = ArchiveReader::new(&dst)
+        .prefix("user.1".to_string())
+        .read_from(&mut archive.as_slice())
+        .expect("Import failed");
+    assert_eq!(summary.records, 1, "One record should be imported");
+    assert!(summary.skipped.is_empty(), "No record should be skipped");
+    assert_eq!(dst.get("user.1").unwrap(), Some("alice".to_string()));
+    assert_eq!(dst.get("user.2").unwrap_or(None), None);
+
+    // a truncated archive imports the complete records only.
+    let truncated = &archive[..archive.len() - 1];
+    let summary = ArchiveReader::new(&dst)
+        .read_from(&mut &truncated[..])
+        .expect("Import failed");
+    assert_eq!(summary.records, 1, "The complete record should be imported");
+    assert!(summary.truncated, "The archive should be truncated");
+}
+
+#[test]
+fn test_k2hash_backup_to() {
+    let k2h_backup = "test_k2hash_backup_to.k2h";
+    let _ = std::fs::remove_file(k2h_backup);
+    let db = K2hash::open_mem().expect("open_mem failed");
+    assert!(
+        db.enable_mtime(true).is_ok(),
+        "Enable mtime operation failed"
+    );
+    assert!(db.set("hello", "world").is_ok(), "Set operation failed");
+    assert!(
+        db.set_attribute("hello", "owner", "alice").is_ok(),
+        "Set attribute operation failed"
+    );
+    let report = db
+        .backup_to(k2h_backup, BackupOptions::new().snapshot(true))
+        .expect("Backup operation failed");
+    assert_eq!(report.keys, 1, "One key should be copied");
+    assert_eq!(report.skipped, 0, "No key should be skipped");
+    assert!(
+        !std::path::Path::new(&format!("{}.snapshot", k2h_backup)).exists(),
+        "The snapshot transaction file should be removed"
+    );
+    {
+        let backup = K2hash::open(k2h_backup).expect("open failed");
+        assert_eq!(backup.get("hello").unwrap(), Some("world".to_string()));
+        let attrs = backup
+            .get_attributes("hello")
+            .expect("Get attributes operation failed")
+            .expect("The attributes should be copied");
+        assert_eq!(attrs.get("owner"), Some(&"alice".to_string()));
+    }
+    // nothing is modified since the future.
+    let report = db
+        .backup_to(
+            k2h_backup,
+            BackupOptions::new().since(SystemTime::now() + Duration::from_secs(3600)),
+        )
+        .expect("Incremental backup operation failed");
+    assert_eq!(report.keys, 0, "No key should be copied");
+    assert_eq!(report.unmodified, 1, "The key should be unmodified");
+    // a snapshot keeps the transaction of the caller enabled by refusing to run.
+    let k2h_tx_log = "test_k2hash_backup_to.log";
+    let tx = db
+        .transaction(k2h_tx_log)
+        .expect("Begin transaction operation failed");
+    assert!(
+        db.backup_to(k2h_backup, BackupOptions::new().snapshot(true))
+            .is_err(),
+        "Snapshot should be refused while a transaction is enabled"
+    );
+    assert!(tx.fd().is_ok(), "The transaction should stay enabled");
+    drop(tx);
+    let _ = std::fs::remove_file(k2h_tx_log);
+}
+
+#[test]
+fn test_k2hash_compact() {
+    let k2h_file = "test_k2hash_compact.k2h";
+    let _ = std::fs::remove_file(k2h_file);
+    let mut db = K2hash::open(k2h_file).expect("open failed");
+    for i in 0..100 {
+        assert!(
+            db.set(&format!("key{}", i), "value").is_ok(),
+            "Set operation failed"
+        );
+    }
+    for i in 1..100 {
+        assert!(
+            db.remove(&format!("key{}", i)).is_ok(),
+            "Remove operation failed"
+        );
+    }
+    let report = db
+        .compact(CompactOptions::new().maxelementcnt(32))
+        .expect("Compact operation failed");
+    assert_eq!(report.keys, 1, "One key should be copied");
+    assert!(
+        report.new_size > 0,
+        "The compacted file should not be empty"
+    );
+    assert_eq!(db.get("key0").unwrap(), Some("value".to_string()));
+    assert!(db.set("key1", "again").is_ok(), "Set operation failed");
+    assert!(
+        db.compact_into(k2h_file, CompactOptions::new()).is_err(),
+        "Compacting into an existing file should fail"
+    );
+}
+
+#[test]
+fn test_k2hash_compact_keeps_settings() {
+    let k2h_file = "test_k2hash_compact_keeps_settings.k2h";
+    let k2h_tx_log = "test_k2hash_compact_keeps_settings.log";
+    let _ = std::fs::remove_file(k2h_file);
+    let mut db = K2hash::open(k2h_file).expect("open failed");
+    assert!(db.enable_mtime(true).is_ok(), "Enable mtime failed");
+    assert!(
+        db.set_default_encryption_password("secret").is_ok(),
+        "Set default encryption password failed"
+    );
+    assert!(
+        db.enable_encryption(true).is_ok(),
+        "Enable encryption failed"
+    );
+    assert!(db.set("hello", "world").is_ok(), "Set operation failed");
+    assert!(
+        db.begin_tx(k2h_tx_log).is_ok(),
+        "Begin transaction operation failed"
+    );
+    assert!(
+        db.compact(CompactOptions::new()).is_err(),
+        "Compacting with a transaction enabled should fail"
+    );
+    assert!(db.stop_tx().is_ok(), "Stop transaction operation failed");
+    let _ = std::fs::remove_file(k2h_tx_log);
+    db.compact(CompactOptions::new())
+        .expect("Compact operation failed");
+    assert_eq!(db.get("hello").unwrap(), Some("world".to_string()));
+    assert!(db.set("again", "value").is_ok(), "Set operation failed");
+    assert_eq!(db.is_encrypted("again"), Ok(true));
+    let metadata = db.get_metadata("again").expect("Get metadata failed");
+    assert!(metadata.mtime.is_some(), "mtime should be recorded");
+}
+
+#[test]
+fn test_k2hash_dump_to_writer() {
+    let db = K2hash::open_mem().expect("open_mem failed");
+    assert!(db.set("hello", "world").is_ok(), "Set operation failed");
+    let mut output = Vec::new();
+    assert!(
+        db.dump_table_stats_to(&mut output, DumpLevel::HEADER)
+            .is_ok(),
+        "Dump table stats operation failed"
+    );
+    assert!(!output.is_empty(), "Table stats should be written");
+    let mut output = Vec::new();
+    assert!(
+        db.dump_data_stats_to(&mut output).is_ok(),
+        "Dump data stats operation failed"
+    );
+    assert!(!output.is_empty(), "Data stats should be written");
+    let mut output = Vec::new();
+    assert!(
+        K2hash::version_to(&mut output).is_ok(),
+        "Version operation failed"
+    );
+    assert!(!output.is_empty(), "Version should be written");
+    let q = Queue::new(db.handle(), true, None, None, None).expect("Queue creation failed");
+    assert!(q.put("hello").is_ok(), "Push operation failed");
+    let mut output = Vec::new();
+    assert!(
+        q.dump_to(&mut output).is_ok(),
+        "Queue dump operation failed"
+    );
+    assert!(!output.is_empty(), "Queue dump should be written");
+    let stats = db
+        .dump_data_stats_string()
+        .expect("Dump data stats operation failed");
+    assert!(!stats.is_empty(), "Data stats should be returned");
+    let version = K2hash::version_string().expect("Version operation failed");
+    assert!(!version.is_empty(), "Version should be returned");
+    let dump = q.dump_string().expect("Queue dump operation failed");
+    assert!(!dump.is_empty(), "Queue dump should be returned");
+}
+
+#[test]
+fn test_k2hash_stats() {
+    let db = K2hash::open_mem().expect("open_mem failed");
+    assert!(db.set("hello", "world").is_ok(), "Set operation failed");
+    let stats = db.stats().expect("Stats operation failed");
+    assert!(stats.total_size > 0, "Total size should be positive");
+    assert!(
+        stats.total_used_size <= stats.total_size,
+        "Used size should not exceed the total size"
+    );
+    assert!(stats.assigned_key_count >= 1, "The key should be assigned");
+    assert_eq!(
+        stats.collision_mask_bits(),
+        stats.collision_mask.count_ones(),
+        "Collision mask bits should count the collision mask"
+    );
+    assert!(
+        (0.0..=1.0).contains(&stats.fill_ratio()),
+        "Fill ratio should be between 0 and 1"
+    );
+}
+
+#[test]
+fn test_k2hash_library_version() {
+    let version = K2hash::library_version().expect("Library version query failed");
+    assert!(
+        version >= MIN_LIBRARY_VERSION,
+        "Library version should be supported"
+    );
+    assert_eq!(
+        version.to_string(),
+        format!("{}.{}.{}", version.major, version.minor, version.patch)
+    );
+    assert!(
+        K2hash::open_mem().is_ok(),
+        "open_mem should pass the version check"
+    );
+}
+
+#[test]
+fn test_k2hash_get_metadata() {
+    let db = K2hash::open_mem().expect("open_mem failed");
+    assert!(
+        db.enable_mtime(true).is_ok(),
+        "Enable mtime operation failed"
+    );
+    let before = std::time::SystemTime::now() - std::time::Duration::from_secs(1);
+    assert!(db.set("hello", "world").is_ok(), "Set operation failed");
+    assert!(
+        db.set_attribute("hello", "owner", "alice").is_ok(),
+        "Set attribute operation failed"
+    );
+    let raw = db
+        .get_attributes_raw("hello")
+        .expect("Get attributes raw operation failed");
+    assert_eq!(raw.get(b"owner\0".as_slice()), Some(&b"alice\0".to_vec()));
+    assert!(
+        raw.keys().any(|name| name.starts_with(b"mtime")),
+        "The mtime attribute should be returned"
+    );
+    let metadata = db
+        .get_metadata("hello")
+        .expect("Get metadata operation failed");
+    assert!(
+        metadata.mtime.is_some_and(|mtime| mtime >= before),
+        "The mtime should be decoded"
+    );
+    assert!(metadata.expire.is_none(), "The key should not expire");
+    assert!(!metadata.encrypted, "The value should not be encrypted");
+    assert_eq!(metadata.custom.len(), 1, "Only owner should be custom");
+    assert!(db.get_metadata("").is_err(), "Empty key should be refused");
+    assert!(
+        db.get_metadata("missing").is_err(),
+        "Missing key should be an error"
+    );
+    assert!(
+        db.get_attributes_raw("missing").is_err(),
+        "Missing key should be an error"
+    );
+}
+
+#[test]
+fn test_k2hash_update_attributes() {
+    let db = K2hash::open_mem().expect("open_mem failed");
+    assert!(
+        db.enable_mtime(true).is_ok(),
+        "Enable mtime operation failed"
+    );
+    assert!(db.set("hello", "world").is_ok(), "Set operation failed");
+    assert!(
+        db.add_subkey("hello", "sub", "val").is_ok(),
+        "Add subkey operation failed"
+    );
+    assert!(
+        db.set_attribute("hello", "owner", "alice").is_ok(),
+        "Set attribute operation failed"
+    );
+    assert!(
+        db.set_attribute("hello", "team", "infra").is_ok(),
+        "Set attribute operation failed"
+    );
+    assert!(
+        db.replace_attribute("hello", "owner", "bob").is_ok(),
+        "Replace attribute operation failed"
+    );
+    let attrs = db
+        .get_attributes("hello")
+        .expect("Get attributes operation failed")
+        .expect("Attributes should exist");
+    assert_eq!(attrs.get("owner"), Some(&"bob".to_string()));
+    assert_eq!(attrs.get("team"), Some(&"infra".to_string()));
+    assert_eq!(db.remove_attribute("hello", "team"), Ok(true));
+    assert_eq!(db.remove_attribute("hello", "team"), Ok(false));
+    assert!(
+        db.remove_attribute("hello", "mtime").is_err(),
+        "Builtin attributes should be kept"
+    );
+    assert!(
+        db.set_attribute("hello", "region", "jp").is_ok(),
+        "Set attribute operation failed"
+    );
+    assert_eq!(db.clear_custom_attributes("hello"), Ok(2));
+    let attrs = db
+        .get_attributes("hello")
+        .expect("Get attributes operation failed")
+        .expect("Attributes should exist");
+    assert!(attrs.contains_key("mtime"), "mtime should be preserved");
+    assert!(!attrs.contains_key("owner"), "owner should be removed");
+    assert!(!attrs.contains_key("region"), "region should be removed");
+    assert_eq!(db.get("hello"), Ok(Some("world".to_string())));
+    assert_eq!(db.get_subkeys("hello"), Ok(Some(vec!["sub".to_string()])));
+    assert!(
+        db.replace_attribute("missing", "owner", "bob").is_err(),
+        "Missing key should be refused"
+    );
+}
+
+#[test]
+fn test_k2hash_update_attributes_with_builtin_attributes() {
+    let db = K2hash::open_mem().expect("open_mem failed");
+    assert!(
+        db.set_default_encryption_password("secret").is_ok(),
+        "Set default encryption password failed"
+    );
+    assert!(
+        db.enable_encryption(true).is_ok(),
+        "Enable encryption failed"
+    );
+    assert!(db.enable_history(true).is_ok(), "Enable history failed");
+    assert!(db.enable_mtime(true).is_ok(), "Enable mtime failed");
+    assert!(db.set("hello", "v1").is_ok(), "Set operation failed");
+    assert!(db.set("hello", "v2").is_ok(), "Set operation failed");
+    assert!(
+        db.set_attribute("hello", "owner", "alice").is_ok(),
+        "Set attribute operation failed"
+    );
+    let before = db.get_metadata("hello").expect("Get metadata failed");
+    let history = db.history("hello").expect("History operation failed");
+    assert!(
+        db.replace_attribute("hello", "owner", "bob").is_ok(),
+        "Replace attribute operation failed"
+    );
+    assert_eq!(db.remove_attribute("hello", "owner"), Ok(true));
+    let after = db.get_metadata("hello").expect("Get metadata failed");
+    assert_eq!(after.mtime, before.mtime, "mtime should not be renewed");
+    assert_eq!(
+        after.history_keys, before.history_keys,
+        "No history should be recorded"
+    );
+    assert_eq!(
+        db.history("hello").expect("History operation failed").len(),
+        history.len()
+    );
+    assert!(after.encrypted, "The value should stay encrypted");
+    assert!(after.custom.is_empty(), "owner should be removed");
+    assert_eq!(db.get("hello"), Ok(Some("v2".to_string())));
+}
+
+#[test]
+fn test_k2hash_history() {
+    let db = K2hash::open_mem().expect("open_mem failed");
+    assert!(
+        db.enable_history(true).is_ok(),
+        "Enable history operation failed"
+    );
+    assert!(
+        db.enable_mtime(true).is_ok(),
+        "Enable mtime operation failed"
+    );
+    for value in ["v1", "v2", "v3"] {
+        assert!(db.set("conf", value).is_ok(), "Set operation failed");
+    }
+    let history = db.history("conf").expect("History operation failed");
+    let values: Vec<String> = history.iter().map(|entry| entry.value_str()).collect();
+    assert_eq!(values, vec!["v2".to_string(), "v1".to_string()]);
+    assert!(
+        history.iter().all(|entry| entry.mtime.is_some()),
+        "Previous values should have mtimes"
+    );
+    let version = db
+        .get_version("conf", 1)
+        .expect("Get version operation failed");
+    assert_eq!(
+        version.map(|entry| entry.value_str()),
+        Some("v1".to_string())
+    );
+    assert_eq!(db.get_version("conf", 5), Ok(None));
+    assert!(
+        db.restore_version("conf", 1).is_ok(),
+        "Restore version operation failed"
+    );
+    assert_eq!(db.get("conf"), Ok(Some("v1".to_string())));
+    assert!(
+        db.restore_version("conf", 5).is_err(),
+        "Missing version should be refused"
+    );
+    for value in ["s1", "s2"] {
+        assert!(
+            db.set_with_options("secret", value, Some("mypass"), None)
+                .is_ok(),
+            "Set operation failed"
+        );
+    }
+    assert!(
+        db.restore_version("secret", 0).is_err(),
+        "Encrypted version should be refused"
+    );
+    assert_eq!(
+        db.get_with_options("secret", Some("mypass")),
+        Ok(Some("s2".to_string()))
+    );
+}
+
+#[test]
+fn test_k2hash_rotate_password() {
+    let db = K2hash::open_mem().expect("open_mem failed");
+    assert!(
+        db.set_with_options("secret1", "s1", Some("oldpass"), None)
+            .is_ok(),
+        "Set operation failed"
+    );
+    assert!(
+        db.set_with_options("secret2", "s2", Some("otherpass"), None)
+            .is_ok(),
+        "Set operation failed"
+    );
+    assert!(db.set("plain", "p").is_ok(), "Set operation failed");
+    assert_eq!(db.is_encrypted("secret1"), Ok(true));
+    assert_eq!(db.is_encrypted("plain"), Ok(false));
+    let mut calls = 0;
+    let report = db
+        .rotate_password_with_progress("oldpass", "newpass", |key| key != "secret3", |_| calls += 1)
+        .expect("Rotate password operation failed");
+    assert_eq!(report.checked, 3, "All keys should be checked");
+    assert_eq!(report.rotated, 1, "secret1 should be rotated");
+    assert_eq!(report.skipped, 1, "plain should be skipped");
+    assert_eq!(report.failures.len(), 1, "secret2 should fail");
+    assert_eq!(report.failures[0].key, "secret2");
+    assert_eq!(calls, 3, "Progress should be reported per key");
+    assert_eq!(
+        db.get_with_options("secret1", Some("newpass")),
+        Ok(Some("s1".to_string()))
+    );
+    assert!(
+        db.get_with_options("secret1", Some("oldpass")).is_err(),
+        "The old password should not decrypt the value"
+    );
+    let report = db
+        .rotate_password("newpass", "nextpass", |key| key.starts_with("secret1"))
+        .expect("Rotate password operation failed");
+    assert_eq!(report.rotated, 1, "Only secret1 should be rotated");
+}
+
+#[test]
+fn test_k2hash_rotate_password_keeps_expire() {
+    let db = K2hash::open_mem().expect("open_mem failed");
+    assert!(
+        db.set_with_options("expiring", "e", Some("oldpass"), Some(3600))
+            .is_ok(),
+        "Set operation failed"
+    );
+    assert!(
+        db.is_encrypted("missing").is_err(),
+        "Missing key should be refused"
+    );
+    let before = db
+        .get_metadata("expiring")
+        .expect("Get metadata failed")
+        .expire
+        .expect("The key should have an expire");
+    let report = db
+        .rotate_password("oldpass", "newpass", |_| true)
+        .expect("Rotate password operation failed");
+    assert_eq!(report.rotated, 1, "expiring should be rotated");
+    let after = db
+        .get_metadata("expiring")
+        .expect("Get metadata failed")
+        .expire
+        .expect("The expire should be kept");
+    let drift = after
+        .duration_since(before)
+        .unwrap_or_else(|e| e.duration());
+    assert!(
+        drift <= Duration::from_secs(2),
+        "The expire should not be reset"
+    );
+    assert_eq!(
+        db.get_with_options("expiring", Some("newpass")),
+        Ok(Some("e".to_string()))
+    );
+}
+
 //
 // Local variables:
 // tab-width: 4
@@ -0,0 +1,75 @@
+//
+// k2hash_rust
+//
+// Copyright 2025 LY Corporation.
+//
+// Rust driver for k2hash that is a NoSQL Key Value Store(KVS) library.
+// For k2hash, see https://github.com/yahoojapan/k2hash for the details.
+//
+// For the full copyright and license information, please view
+// the license file that was distributed with this source code.
+//
+// AUTHOR:   Hirotaka Wakabayashi
+// CREATE:   Fri, 17 Jul 2025
+// REVISION:
+//
+
+#![cfg(feature = "libk2hash")]
+
+// The debug settings of libk2hash are process wide, so this test runs in its own binary
+// and puts them back when it ends.
+
+use k2hash_rust::{DebugLevel, K2hash};
+
+const DEBUG_FILE: &str = "test_debug.log";
+
+// Restores the silent level, the stderr output and the environment even if an assertion fails.
+struct RestoreDebug;
+
+impl Drop for RestoreDebug {
+    fn drop(&mut self) {
+        let _ = K2hash::clear_debug_file();
+        let _ = K2hash::set_debug_level(DebugLevel::SILENT);
+        std::env::remove_var("K2HDBGMODE");
+        let _ = std::fs::remove_file(DEBUG_FILE);
+    }
+}
+
+#[test]
+fn test_k2hash_debug_file() {
+    let _restore = RestoreDebug;
+    let _ = std::fs::remove_file(DEBUG_FILE);
+    assert!(
+        K2hash::set_debug_file("").is_err(),
+        "Empty path should be refused"
+    );
+    assert!(
+        K2hash::set_debug_file(DEBUG_FILE).is_ok(),
+        "Set debug file operation failed"
+    );
+    assert!(
+        std::path::Path::new(DEBUG_FILE).exists(),
+        "Debug file should be created"
+    );
+    assert!(
+        K2hash::clear_debug_file().is_ok(),
+        "Clear debug file operation failed"
+    );
+    assert!(
+        K2hash::enable_debug_bumpup_on_sigusr1().is_ok(),
+        "Enable bumpup operation failed"
+    );
+    std::env::set_var("K2HDBGMODE", "SILENT");
+    assert!(
+        K2hash::load_debug_from_env().is_ok(),
+        "Load debug env operation failed"
+    );
+}
+//
+// Local variables:
+// tab-width: 4
+// c-basic-offset: 4
+// End:
+// vim600: expandtab sw=4 ts=4 fdm=marker
+// vim<600: expandtab sw=4 ts=4
+//
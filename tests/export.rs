@@ -0,0 +1,98 @@
+//
+// k2hash_rust
+//
+// Copyright 2025 LY Corporation.
+//
+// Rust driver for k2hash that is a NoSQL Key Value Store(KVS) library.
+// For k2hash, see https://github.com/yahoojapan/k2hash for the details.
+//
+// For the full copyright and license information, please view
+// the license file that was distributed with this source code.
+//
+// AUTHOR:   Hirotaka Wakabayashi
+// CREATE:   Fri, 17 Jul 2025
+// REVISION:
+//
+
+#![cfg(all(feature = "libk2hash", feature = "export"))]
+
+use k2hash_rust::K2hash;
+
+fn source_db() -> K2hash {
+    let db = K2hash::open_mem().expect("open_mem failed");
+    assert!(db.set("hello", "world").is_ok(), "Set operation failed");
+    assert!(
+        db.add_subkey("hello", "sub", "val").is_ok(),
+        "Add subkey operation failed"
+    );
+    assert!(
+        db.set_attribute("hello", "owner", "alice").is_ok(),
+        "Set attribute operation failed"
+    );
+    db
+}
+
+fn assert_imported(db: &K2hash) {
+    assert_eq!(db.get("hello").unwrap(), Some("world".to_string()));
+    assert_eq!(db.get("sub").unwrap(), Some("val".to_string()));
+    assert_eq!(
+        db.get_subkeys("hello").unwrap(),
+        Some(vec!["sub".to_string()])
+    );
+}
+
+#[test]
+fn test_k2hash_export_import_json() {
+    let src = source_db();
+    let mut data = Vec::new();
+    assert_eq!(
+        src.export_json(&mut data),
+        Ok(2),
+        "Two keys should be exported"
+    );
+    let dst = K2hash::open_mem().expect("open_mem failed");
+    assert_eq!(dst.import_json(&mut data.as_slice()), Ok(2));
+    assert_imported(&dst);
+}
+
+#[test]
+fn test_k2hash_export_import_ndjson() {
+    let src = source_db();
+    let mut data = Vec::new();
+    assert_eq!(
+        src.export_ndjson(&mut data),
+        Ok(2),
+        "Two keys should be exported"
+    );
+    assert_eq!(String::from_utf8_lossy(&data).lines().count(), 2);
+    let dst = K2hash::open_mem().expect("open_mem failed");
+    assert_eq!(dst.import_ndjson(&mut data.as_slice()), Ok(2));
+    assert_imported(&dst);
+}
+
+#[test]
+fn test_k2hash_export_import_csv() {
+    let src = source_db();
+    let mut data = Vec::new();
+    assert_eq!(
+        src.export_csv(&mut data),
+        Ok(2),
+        "Two keys should be exported"
+    );
+    assert!(
+        String::from_utf8_lossy(&data).starts_with("key,value,subkeys,attributes"),
+        "CSV should start with the header"
+    );
+    let dst = K2hash::open_mem().expect("open_mem failed");
+    assert_eq!(dst.import_csv(&mut data.as_slice()), Ok(2));
+    assert_imported(&dst);
+}
+
+//
+// Local variables:
+// tab-width: 4
+// c-basic-offset: 4
+// End:
+// vim600: expandtab sw=4 ts=4 fdm=marker
+// vim<600: expandtab sw=4 ts=4
+//
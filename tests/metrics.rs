@@ -0,0 +1,103 @@
+//
+// k2hash_rust
+//
+// Copyright 2025 LY Corporation.
+//
+// Rust driver for k2hash that is a NoSQL Key Value Store(KVS) library.
+// For k2hash, see https://github.com/yahoojapan/k2hash for the details.
+//
+// For the full copyright and license information, please view
+// the license file that was distributed with this source code.
+//
+// AUTHOR:   Hirotaka Wakabayashi
+// CREATE:   Fri, 17 Jul 2025
+// REVISION:
+//
+
+#![cfg(all(feature = "libk2hash", feature = "metrics"))]
+
+use k2hash_rust::{K2hash, Queue};
+use metrics_util::debugging::{DebugValue, DebuggingRecorder, Snapshotter};
+
+fn find(snapshotter: &Snapshotter, name: &str, label: (&str, &str)) -> Option<DebugValue> {
+    snapshotter
+        .snapshot()
+        .into_vec()
+        .into_iter()
+        .find(|(key, _, _, _)| {
+            key.key().name() == name
+                && key
+                    .key()
+                    .labels()
+                    .any(|l| l.key() == label.0 && l.value() == label.1)
+        })
+        .map(|(_, _, _, value)| value)
+}
+
+#[test]
+fn test_metrics_operations() {
+    let recorder = DebuggingRecorder::new();
+    let snapshotter = recorder.snapshotter();
+    metrics::with_local_recorder(&recorder, || {
+        let db = K2hash::open_mem().expect("open_mem failed");
+        assert!(db.set("hello", "world").is_ok(), "Set operation failed");
+        assert!(
+            db.set("", "world").is_err(),
+            "Set with an empty key should fail"
+        );
+        assert_eq!(db.get("hello"), Ok(Some("world".to_string())));
+    });
+    assert_eq!(
+        find(&snapshotter, "k2hash_operations_total", ("op", "set")),
+        Some(DebugValue::Counter(2))
+    );
+    assert_eq!(
+        find(&snapshotter, "k2hash_errors_total", ("op", "set")),
+        Some(DebugValue::Counter(1))
+    );
+    match find(
+        &snapshotter,
+        "k2hash_operation_duration_seconds",
+        ("op", "get"),
+    ) {
+        Some(DebugValue::Histogram(samples)) => assert_eq!(samples.len(), 1),
+        other => panic!("unexpected histogram: {:?}", other),
+    }
+}
+
+#[test]
+fn test_metrics_queue_depth() {
+    let recorder = DebuggingRecorder::new();
+    let snapshotter = recorder.snapshotter();
+    metrics::with_local_recorder(&recorder, || {
+        let db = K2hash::open_mem().expect("open_mem failed");
+        let q = Queue::new(db.handle(), true, Some("jobs".to_string()), None, None)
+            .expect("Queue creation failed");
+        assert!(q.put("a").is_ok(), "Push operation failed");
+        assert!(q.put("b").is_ok(), "Push operation failed");
+        assert!(q.put("c").is_ok(), "Push operation failed");
+        assert_eq!(q.get(), Some("a".to_string()));
+    });
+    // the depth is sampled on the first push only, as the next sample is not due yet.
+    assert_eq!(
+        find(&snapshotter, "k2hash_queue_depth", ("prefix", "jobs")),
+        Some(DebugValue::Gauge(1.0.into()))
+    );
+    assert_eq!(
+        find(
+            &snapshotter,
+            "k2hash_operations_total",
+            ("op", "queue_push")
+        ),
+        Some(DebugValue::Counter(3))
+    );
+}
+
+//
+// Local variables:
+// tab-width: 4
+// c-basic-offset: 4
+// End:
+// vim600: expandtab sw=4 ts=4 fdm=marker
+// vim<600: expandtab sw=4 ts=4
+//
@@ -0,0 +1,118 @@
+//
+// k2hash_rust
+//
+// Copyright 2025 LY Corporation.
+//
+// Rust driver for k2hash that is a NoSQL Key Value Store(KVS) library.
+// For k2hash, see https://github.com/yahoojapan/k2hash for the details.
+//
+// For the full copyright and license information, please view
+// the license file that was distributed with this source code.
+//
+// AUTHOR:   Hirotaka Wakabayashi
+// CREATE:   Fri, 17 Jul 2025
+// REVISION:
+//
+
+#![cfg(all(feature = "libk2hash", feature = "tokio"))]
+
+use k2hash_rust::{AsyncK2hash, AsyncKeyQueue, AsyncQueue, K2hash, KeyQueue, Queue};
+use std::time::Duration;
+use tokio_stream::StreamExt;
+
+/// Test for AsyncK2hash set and get
+#[tokio::test]
+async fn test_async_k2hash_set_get() {
+    let db = AsyncK2hash::open_mem().await.expect("open_mem failed");
+    assert!(db.handle() != 0, "Database handle should not be zero");
+    assert!(
+        db.set("hello", "world").await.is_ok(),
+        "Set operation failed"
+    );
+    assert_eq!(
+        db.get("hello").await.expect("Get operation failed"),
+        Some("world".to_string()),
+        "Get operation returned unexpected value"
+    );
+    assert!(db.remove("hello").await.is_ok(), "Remove operation failed");
+    assert!(
+        db.get("hello").await.is_err(),
+        "Removed key should not exist"
+    );
+}
+
+// AsyncK2hash::keys
+#[tokio::test]
+async fn test_async_k2hash_keys() {
+    let db = AsyncK2hash::open_mem().await.expect("open_mem failed");
+    assert!(
+        db.set("hello", "world").await.is_ok(),
+        "Set operation failed"
+    );
+    let keys: Vec<String> = db.keys().collect().await;
+    assert_eq!(
+        keys,
+        vec!["hello".to_string()],
+        "Key stream returned unexpected keys"
+    );
+}
+
+/// Test for AsyncQueue operations
+#[tokio::test]
+async fn test_async_queue() {
+    let db = K2hash::open_mem().expect("open_mem failed");
+    let q = Queue::new(db.handle(), true, None, None, None).expect("Queue creation failed");
+    let q = AsyncQueue::new(q);
+    assert!(q.put("hello").await.is_ok(), "Push operation failed");
+    assert!(q.put("world").await.is_ok(), "Push operation failed");
+    assert_eq!(q.qsize().await, 2, "Queue size should be 2");
+    let mut stream = q.into_stream(Duration::from_millis(10));
+    assert_eq!(stream.next().await, Some("hello".to_string()));
+    assert_eq!(stream.next().await, Some("world".to_string()));
+}
+
+/// Test that dropping a queue stream keeps the values it did not return
+#[tokio::test]
+async fn test_async_queue_stream_drop() {
+    let db = K2hash::open_mem().expect("open_mem failed");
+    let q = Queue::new(db.handle(), true, None, None, None).expect("Queue creation failed");
+    let q = AsyncQueue::new(q);
+    for value in ["a", "b", "c"] {
+        assert!(q.put(value).await.is_ok(), "Push operation failed");
+    }
+    let mut stream = q.clone().into_stream(Duration::from_millis(10));
+    assert_eq!(stream.next().await, Some("a".to_string()));
+    drop(stream);
+    assert_eq!(
+        q.qsize().await,
+        2,
+        "Dropping the stream should keep 2 values"
+    );
+    assert_eq!(q.get().await, Some("b".to_string()));
+}
+
+/// Test for AsyncKeyQueue operations
+#[tokio::test]
+async fn test_async_keyqueue() {
+    let db = K2hash::open_mem().expect("open_mem failed");
+    let q = KeyQueue::new(db.handle(), true, None, None, None).expect("KeyQueue creation failed");
+    let q = AsyncKeyQueue::new(q);
+    assert!(
+        q.put("hello", "world").await.is_ok(),
+        "Push operation failed"
+    );
+    assert!(!q.empty().await, "KeyQueue should not be empty");
+    assert_eq!(
+        q.get().await,
+        Some(("hello".to_string(), "world".to_string()))
+    );
+}
+
+//
+// Local variables:
+// tab-width: 4
+// c-basic-offset: 4
+// End:
+// vim600: expandtab sw=4 ts=4 fdm=marker
+// vim<600: expandtab sw=4 ts=4
+//
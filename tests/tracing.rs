@@ -0,0 +1,199 @@
+//
+// k2hash_rust
+//
+// Copyright 2025 LY Corporation.
+//
+// Rust driver for k2hash that is a NoSQL Key Value Store(KVS) library.
+// For k2hash, see https://github.com/yahoojapan/k2hash for the details.
+//
+// For the full copyright and license information, please view
+// the license file that was distributed with this source code.
+//
+// AUTHOR:   Hirotaka Wakabayashi
+// CREATE:   Fri, 17 Jul 2025
+// REVISION:
+//
+
+#![cfg(all(feature = "libk2hash", feature = "tracing"))]
+
+use k2hash_rust::{DebugLevel, K2hash};
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, Once};
+use std::thread;
+use std::time::Duration;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Level, Metadata, Subscriber};
+
+type Fields = Vec<(String, String)>;
+
+/// Recorder keeps the fields of every span and event.
+struct Recorder {
+    next_id: AtomicU64,
+    spans: Mutex<Vec<(u64, Fields)>>,
+    events: Mutex<Vec<(Level, Fields)>>,
+}
+
+static RECORDER: Recorder = Recorder {
+    next_id: AtomicU64::new(1),
+    spans: Mutex::new(Vec::new()),
+    events: Mutex::new(Vec::new()),
+};
+
+struct FieldVisitor<'a>(&'a mut Fields);
+
+impl Visit for FieldVisitor<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.push((field.name().to_string(), value.to_string()));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        self.0
+            .push((field.name().to_string(), format!("{:?}", value)));
+    }
+}
+
+struct RecordingSubscriber;
+
+impl Subscriber for RecordingSubscriber {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata.target() == "k2hash"
+    }
+
+    fn new_span(&self, span: &Attributes<'_>) -> Id {
+        let id = RECORDER.next_id.fetch_add(1, Ordering::Relaxed);
+        let mut fields = Fields::new();
+        span.record(&mut FieldVisitor(&mut fields));
+        RECORDER.spans.lock().unwrap().push((id, fields));
+        Id::from_u64(id)
+    }
+
+    fn record(&self, span: &Id, values: &Record<'_>) {
+        let mut spans = RECORDER.spans.lock().unwrap();
+        if let Some((_, fields)) = spans.iter_mut().find(|(id, _)| *id == span.into_u64()) {
+            values.record(&mut FieldVisitor(fields));
+        }
+    }
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut fields = Fields::new();
+        event.record(&mut FieldVisitor(&mut fields));
+        let level = *event.metadata().level();
+        RECORDER.events.lock().unwrap().push((level, fields));
+    }
+
+    fn enter(&self, _span: &Id) {}
+
+    fn exit(&self, _span: &Id) {}
+}
+
+fn init() {
+    static INIT: Once = Once::new();
+    INIT.call_once(|| {
+        tracing::subscriber::set_global_default(RecordingSubscriber)
+            .expect("set_global_default failed");
+    });
+}
+
+fn field<'a>(fields: &'a Fields, name: &str) -> Option<&'a str> {
+    fields
+        .iter()
+        .find(|(n, _)| n == name)
+        .map(|(_, v)| v.as_str())
+}
+
+#[test]
+fn test_tracing_operation_spans() {
+    init();
+    let db = K2hash::open_mem().expect("open_mem failed");
+    assert!(db.set("tracing", "world").is_ok(), "Set operation failed");
+    assert!(
+        db.set("", "world").is_err(),
+        "Set with an empty key should fail"
+    );
+    assert!(
+        db.set_attribute("tracing", "owner", "alice").is_ok(),
+        "Set attribute operation failed"
+    );
+    assert!(
+        db.rename("tracing", "renamed").is_ok(),
+        "Rename operation failed"
+    );
+    let spans = RECORDER.spans.lock().unwrap();
+    for op in ["set_attribute", "rename"] {
+        assert!(
+            spans
+                .iter()
+                .any(|(_, fields)| field(fields, "op") == Some(op)),
+            "{} should run in a span",
+            op
+        );
+    }
+    let set_spans: Vec<&Fields> = spans
+        .iter()
+        .map(|(_, fields)| fields)
+        .filter(|fields| field(fields, "op") == Some("set"))
+        .collect();
+    assert!(set_spans.iter().any(|fields| {
+        field(fields, "key_len") == Some("7")
+            && field(fields, "outcome") == Some("ok")
+            && field(fields, "elapsed_us").is_some()
+    }));
+    assert!(set_spans
+        .iter()
+        .any(|fields| field(fields, "key_len") == Some("0")
+            && field(fields, "outcome") == Some("error")));
+}
+
+/// Restores the debug output of the C-library even if the test fails.
+struct RestoreDebug;
+
+impl Drop for RestoreDebug {
+    fn drop(&mut self) {
+        let _ = K2hash::clear_debug_file();
+        let _ = K2hash::set_debug_level(DebugLevel::SILENT);
+    }
+}
+
+#[test]
+fn test_tracing_forward_debug_log() {
+    init();
+    let _restore = RestoreDebug;
+    assert!(K2hash::set_debug_level(DebugLevel::ERROR).is_ok());
+    for _ in 0..2 {
+        assert!(
+            K2hash::forward_debug_log().is_ok(),
+            "forward_debug_log failed"
+        );
+    }
+    assert!(K2hash::open("/nonexistent/k2hash_rust/test.k2h").is_err());
+    // libk2hash may buffer the debug file.
+    unsafe { libc::fflush(std::ptr::null_mut()) };
+    let mut forwarded = false;
+    for _ in 0..50 {
+        if RECORDER
+            .events
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|(level, _)| *level == Level::ERROR)
+        {
+            forwarded = true;
+            break;
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+    assert!(forwarded, "The error should be forwarded as an event");
+}
+
+//
+// Local variables:
+// tab-width: 4
+// c-basic-offset: 4
+// End:
+// vim600: expandtab sw=4 ts=4 fdm=marker
+// vim<600: expandtab sw=4 ts=4
+//
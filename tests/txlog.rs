@@ -0,0 +1,88 @@
+//
+// k2hash_rust
+//
+// Copyright 2025 LY Corporation.
+//
+// Rust driver for k2hash that is a NoSQL Key Value Store(KVS) library.
+// For k2hash, see https://github.com/yahoojapan/k2hash for the details.
+//
+// For the full copyright and license information, please view
+// the license file that was distributed with this source code.
+//
+// AUTHOR:   Hirotaka Wakabayashi
+// CREATE:   Fri, 17 Jul 2025
+// REVISION:
+//
+
+use k2hash_rust::{TxLogReader, TxOperation, TxRecord};
+
+/// A transaction file of a set of "hello", a rename to "bye" and a remove of "bye".
+///
+/// The file is hand-built after the `K2HTRANSHEAD` and `K2HCOMMAND` layouts of an LP64 writer
+/// with a fixed timestamp, not written by libk2hash. `regenerate_txlog_fixture` replaces it
+/// with the output of `begin_tx` on a machine with libk2hash.
+const TXLOG_FIXTURE: &str = "tests/fixtures/txlog_basic.log";
+
+/// Rewrites the fixture from `begin_tx`, run by `cargo test -- --ignored regenerate_txlog_fixture`.
+#[test]
+#[ignore]
+#[cfg(feature = "libk2hash")]
+fn regenerate_txlog_fixture() {
+    use k2hash_rust::K2hash;
+    let _ = std::fs::remove_file(TXLOG_FIXTURE);
+    let db = K2hash::open_mem().expect("open_mem failed");
+    assert!(
+        db.begin_tx(TXLOG_FIXTURE).is_ok(),
+        "Begin transaction operation failed"
+    );
+    assert!(db.set("hello", "world").is_ok(), "Set operation failed");
+    assert!(db.rename("hello", "bye").is_ok(), "Rename operation failed");
+    assert!(db.remove("bye").is_ok(), "Remove operation failed");
+    assert!(db.stop_tx().is_ok(), "Stop transaction operation failed");
+}
+
+#[test]
+fn test_txlogreader_fixture() {
+    let records: Vec<TxRecord> = TxLogReader::open(TXLOG_FIXTURE)
+        .expect("Open transaction file failed")
+        .map(|r| r.expect("Read transaction record failed"))
+        .collect();
+    assert!(
+        records
+            .iter()
+            .any(|r| r.key_str() == "hello" && r.value_str() == Some("world".to_string())),
+        "Fixture should have the set record"
+    );
+    assert!(
+        records
+            .iter()
+            .any(|r| r.operation == TxOperation::Rename && r.new_key() == Some("bye".to_string())),
+        "Fixture should have the rename record"
+    );
+    assert!(
+        records
+            .iter()
+            .any(|r| r.key_str() == "bye" && r.operation == TxOperation::Remove),
+        "Fixture should have the remove record"
+    );
+    // a broken length at the tail is read as a truncated record without allocating it.
+    let mut data = std::fs::read(TXLOG_FIXTURE).expect("Read fixture failed");
+    let length = data.len();
+    data.extend_from_slice(&(u32::MAX as u64).to_ne_bytes());
+    data.extend_from_slice(&[0u8; 116]);
+    let mut reader = TxLogReader::new(std::io::Cursor::new(data));
+    assert_eq!(reader.by_ref().count(), records.len());
+    assert!(
+        reader.is_truncated(),
+        "The broken record should be truncated"
+    );
+    assert_eq!(reader.offset(), length as u64);
+}
+//
+// Local variables:
+// tab-width: 4
+// c-basic-offset: 4
+// End:
+// vim600: expandtab sw=4 ts=4 fdm=marker
+// vim<600: expandtab sw=4 ts=4
+//